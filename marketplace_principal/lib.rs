@@ -82,1385 +82,6117 @@ Simulación de pagos.
 */
 
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
+// El código que genera `#[ink::contract]` dispara este lint en el nightly fijado.
+#![allow(non_local_definitions)]
+
+// Reexporta el contrato y sus tipos públicos para que otros contratos (como
+// `ReportesView`) puedan consumirlo con la feature `ink-as-dependency`.
+pub use self::marketplace_principal::{
+    Disputa, EstadoOrden, EstadoProducto, LineaOrden, MarketplacePrincipal, MarketplacePrincipalRef, Moneda, Orden,
+    Pagina, Permiso, Permisos, Producto, ProgresoMigracion, Reputacion, ResolucionDisputa, ResumenReputacion, RolUsuario, SistemaError,
+    Usuario, BPS_TOTAL, COMISION_MAXIMA_BPS, ESCALA_PROMEDIO, LIMITE_CARRITO, LIMITE_PAGINA, PLAZO_CONFIRMACION,
+    PLAZO_ENVIO, VERSION_ALMACENAMIENTO,
+};
+
+use ink::prelude::string::String;
+use ink::prelude::vec::Vec;
+use ink::primitives::AccountId;
+
+/// Consultas de solo lectura que el marketplace ofrece a otros contratos.
+///
+/// `ReportesView` depende únicamente de este trait, de modo que el sistema de tipos
+/// le impide invocar mensajes que modifican el estado del marketplace.
+#[ink::trait_definition]
+pub trait MarketplaceConsultas {
+    /// Lista los usuarios registrados, en orden de registro, a partir de la posición `desde`.
+    ///
+    /// Devuelve como máximo `limite` usuarios (acotado por `LIMITE_PAGINA`) y el
+    /// cursor a usar como `desde` en la próxima llamada, o `None` si no hay más.
+    #[ink(message)]
+    fn listar_usuarios(&self, desde: u32, limite: u32) -> Pagina<Usuario>;
+
+    /// Lista los productos del catálogo a partir del id `desde`, con el mismo
+    /// criterio de paginado que `listar_usuarios`.
+    #[ink(message)]
+    fn listar_productos(&self, desde: u32, limite: u32) -> Pagina<Producto>;
+
+    /// Lista las órdenes a partir del id `desde`, con el mismo criterio de
+    /// paginado que `listar_usuarios`.
+    #[ink(message)]
+    fn listar_ordenes(&self, desde: u32, limite: u32) -> Pagina<Orden>;
+
+    /// Lista las órdenes en las que la cuenta participa como compradora, en orden de
+    /// creación, a partir de la posición `desde`, con el mismo criterio de paginado
+    /// que `listar_usuarios`.
+    #[ink(message)]
+    fn ordenes_como_comprador(&self, cuenta: AccountId, desde: u32, limite: u32) -> Pagina<Orden>;
+
+    /// Lista las órdenes en las que la cuenta participa como vendedora, con el mismo
+    /// criterio que `ordenes_como_comprador`.
+    #[ink(message)]
+    fn ordenes_como_vendedor(&self, cuenta: AccountId, desde: u32, limite: u32) -> Pagina<Orden>;
+}
+
+/// Errores del estándar PSP22.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub enum PSP22Error {
+    /// Error propio de la implementación del token.
+    Custom(String),
+    /// El dueño no tiene saldo suficiente.
+    InsufficientBalance,
+    /// El gastador no tiene autorización suficiente.
+    InsufficientAllowance,
+}
+
+/// Interfaz de un token fungible PSP22, con los selectores del estándar.
+///
+/// El marketplace la usa para cobrar y liquidar órdenes de productos con precio
+/// en un token permitido.
+#[ink::trait_definition]
+pub trait PSP22 {
+    /// Cantidad total de tokens emitidos.
+    #[ink(message)]
+    fn total_supply(&self) -> u128;
+
+    /// Saldo de una cuenta.
+    #[ink(message)]
+    fn balance_of(&self, owner: AccountId) -> u128;
+
+    /// Monto que `spender` puede transferir en nombre de `owner`.
+    #[ink(message)]
+    fn allowance(&self, owner: AccountId, spender: AccountId) -> u128;
+
+    /// Transfiere `value` tokens del caller a `to`.
+    #[ink(message)]
+    fn transfer(&mut self, to: AccountId, value: u128, data: Vec<u8>) -> Result<(), PSP22Error>;
+
+    /// Transfiere `value` tokens de `from` a `to` usando la autorización del caller.
+    #[ink(message)]
+    fn transfer_from(&mut self, from: AccountId, to: AccountId, value: u128, data: Vec<u8>) -> Result<(), PSP22Error>;
+
+    /// Autoriza a `spender` a transferir hasta `value` tokens del caller.
+    #[ink(message)]
+    fn approve(&mut self, spender: AccountId, value: u128) -> Result<(), PSP22Error>;
+
+    /// Suma `delta_value` a la autorización de `spender`.
+    #[ink(message)]
+    fn increase_allowance(&mut self, spender: AccountId, delta_value: u128) -> Result<(), PSP22Error>;
+
+    /// Resta `delta_value` a la autorización de `spender`.
+    #[ink(message)]
+    fn decrease_allowance(&mut self, spender: AccountId, delta_value: u128) -> Result<(), PSP22Error>;
+}
 
 #[ink::contract]
 mod marketplace_principal {
-    // Importa los derive macros y tipos
-    use parity_scale_codec::{Encode, Decode};
-    use scale_info::TypeInfo;
-    use ink::storage::traits::StorageLayout;
-    use ink::storage::Mapping;
+    // Importa los tipos de almacenamiento y colecciones
+    use ink::storage::{Lazy, Mapping};
+    use ink::storage::traits::{Packed, StorageKey};
+    use crate::{MarketplaceConsultas, PSP22};
+    use ink::codegen::TraitCallBuilder;
     use ink::prelude::string::String;
     use ink::prelude::vec::Vec;
 
     /// Estructura principal del contrato Marketplace.
+    ///
+    /// Los campos que se agreguen en versiones futuras deben ser `Lazy` o `Mapping`:
+    /// no ocupan lugar en la celda raíz, así que el contrato actualizado puede seguir
+    /// decodificando el estado guardado por la versión anterior.
     #[ink(storage)]
     pub struct MarketplacePrincipal {
         /// Mapeo de usuarios registrados (por dirección).
         usuarios: Mapping<AccountId, Usuario>,
-        /// Lista de productos publicados.
-        productos: Vec<Producto>,
-        /// Lista de órdenes generadas.
-        ordenes: Vec<Orden>,
+        /// Cuentas registradas en orden de registro, para poder recorrer los usuarios.
+        cuentas_registradas: Mapping<u32, AccountId>,
+        /// Cantidad de usuarios registrados.
+        cantidad_usuarios: u32,
+        /// Productos publicados (por id).
+        productos: Mapping<u32, Producto>,
+        /// Id que se asignará al próximo producto publicado.
+        siguiente_id_producto: u32,
+        /// Órdenes generadas (por id).
+        ordenes: Mapping<u32, Orden>,
+        /// Id que se asignará a la próxima orden creada.
+        siguiente_id_orden: u32,
+        /// Ids de los productos publicados por cada vendedor, por (vendedor, posición).
+        productos_por_vendedor: Mapping<(AccountId, u32), u32>,
+        /// Cantidad de productos publicados por cada vendedor.
+        cantidad_productos_por_vendedor: Mapping<AccountId, u32>,
+        /// Ids de las órdenes de cada cuenta como compradora, por (cuenta, posición).
+        ordenes_por_comprador: Mapping<(AccountId, u32), u32>,
+        /// Cantidad de órdenes de cada cuenta como compradora.
+        cantidad_ordenes_por_comprador: Mapping<AccountId, u32>,
+        /// Ids de las órdenes de cada cuenta como vendedora, por (cuenta, posición).
+        ordenes_por_vendedor: Mapping<(AccountId, u32), u32>,
+        /// Cantidad de órdenes de cada cuenta como vendedora.
+        cantidad_ordenes_por_vendedor: Mapping<AccountId, u32>,
+        /// Cuenta dueña del contrato; administra a los administradores.
+        propietario: AccountId,
+        /// Cuentas habilitadas para pausar y reanudar el contrato.
+        administradores: Vec<AccountId>,
+        /// Si está en `true`, se rechazan todos los mensajes que modifican el estado.
+        pausado: bool,
+        /// Versión del formato con el que están guardados los registros.
+        version_almacenamiento: u32,
+        /// Comisión del marketplace en puntos básicos sobre cada pago liquidado al vendedor.
+        comision_bps: Lazy<u16>,
+        /// Comisiones cobradas pendientes de retiro por el propietario.
+        tesoreria: Lazy<Balance>,
+        /// Pagos liquidados a cada vendedor y reembolsos a compradores pendientes de retiro.
+        saldos: Mapping<AccountId, Balance>,
+        /// Tokens PSP22 en los que se pueden publicar productos.
+        tokens_permitidos: Mapping<AccountId, ()>,
+        /// Pagos y reembolsos en tokens pendientes de retiro, por (cuenta, token).
+        saldos_token: Mapping<(AccountId, AccountId), Balance>,
+        /// Comisiones cobradas en cada token pendientes de retiro.
+        tesoreria_token: Mapping<AccountId, Balance>,
+        /// Avance de la migración en curso, cuando `migrar` la reparte en varias llamadas.
+        migracion: Lazy<ProgresoMigracion>,
+        /// Permisos que un moderador revocó a cada cuenta y que el usuario no puede
+        /// recuperar cambiando de rol.
+        permisos_bloqueados: Mapping<AccountId, Permisos>,
+    }
+
+    impl Default for MarketplacePrincipal {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl MarketplaceConsultas for MarketplacePrincipal {
+        /// Lista los usuarios registrados, en orden de registro, a partir de la posición `desde`.
+        ///
+        /// Devuelve como máximo `limite` usuarios (acotado por `LIMITE_PAGINA`) y el
+        /// cursor a usar como `desde` en la próxima llamada, o `None` si no hay más.
+        #[ink(message)]
+        fn listar_usuarios(&self, desde: u32, limite: u32) -> Pagina<Usuario> {
+            let (posiciones, siguiente) = Self::rango_pagina(desde, limite, self.cantidad_usuarios);
+            Pagina {
+                elementos: posiciones
+                    .filter_map(|posicion| self.cuentas_registradas.get(posicion))
+                    .filter_map(|cuenta| self.usuarios.get(cuenta))
+                    .collect(),
+                siguiente,
+            }
+        }
+
+        /// Lista los productos del catálogo a partir del id `desde`.
+        ///
+        /// Devuelve como máximo `limite` productos (acotado por `LIMITE_PAGINA`) y el
+        /// cursor a usar como `desde` en la próxima llamada, o `None` si no hay más.
+        #[ink(message)]
+        fn listar_productos(&self, desde: u32, limite: u32) -> Pagina<Producto> {
+            let (ids, siguiente) = Self::rango_pagina(desde, limite, self.siguiente_id_producto);
+            Pagina {
+                elementos: ids.filter_map(|id| self.productos.get(id)).collect(),
+                siguiente,
+            }
+        }
+
+        /// Lista las órdenes a partir del id `desde`.
+        ///
+        /// Devuelve como máximo `limite` órdenes (acotado por `LIMITE_PAGINA`) y el
+        /// cursor a usar como `desde` en la próxima llamada, o `None` si no hay más.
+        #[ink(message)]
+        fn listar_ordenes(&self, desde: u32, limite: u32) -> Pagina<Orden> {
+            let (ids, siguiente) = Self::rango_pagina(desde, limite, self.siguiente_id_orden);
+            Pagina {
+                elementos: ids.filter_map(|id| self.ordenes.get(id)).collect(),
+                siguiente,
+            }
+        }
+
+        /// Lista las órdenes en las que la cuenta participa como compradora, en orden
+        /// de creación, a partir de la posición `desde`.
+        ///
+        /// Devuelve como máximo `limite` órdenes (acotado por `LIMITE_PAGINA`) y el
+        /// cursor a usar como `desde` en la próxima llamada, o `None` si no hay más.
+        #[ink(message)]
+        fn ordenes_como_comprador(&self, cuenta: AccountId, desde: u32, limite: u32) -> Pagina<Orden> {
+            let total = self.cantidad_ordenes_por_comprador.get(cuenta).unwrap_or(0);
+            let (posiciones, siguiente) = Self::rango_pagina(desde, limite, total);
+            Pagina {
+                elementos: posiciones
+                    .filter_map(|posicion| self.ordenes_por_comprador.get((cuenta, posicion)))
+                    .filter_map(|id| self.ordenes.get(id))
+                    .collect(),
+                siguiente,
+            }
+        }
+
+        /// Lista las órdenes en las que la cuenta participa como vendedora, con el
+        /// mismo criterio que `ordenes_como_comprador`.
+        #[ink(message)]
+        fn ordenes_como_vendedor(&self, cuenta: AccountId, desde: u32, limite: u32) -> Pagina<Orden> {
+            let total = self.cantidad_ordenes_por_vendedor.get(cuenta).unwrap_or(0);
+            let (posiciones, siguiente) = Self::rango_pagina(desde, limite, total);
+            Pagina {
+                elementos: posiciones
+                    .filter_map(|posicion| self.ordenes_por_vendedor.get((cuenta, posicion)))
+                    .filter_map(|id| self.ordenes.get(id))
+                    .collect(),
+                siguiente,
+            }
+        }
     }
 
     impl MarketplacePrincipal {
         /// Crea una nueva instancia vacía del marketplace.
         #[ink(constructor)]
         pub fn new() -> Self {
-            Self {
+            Self::con_personal(Vec::new(), Vec::new())
+        }
+
+        /// Crea una nueva instancia vacía del marketplace con un conjunto de árbitros
+        /// habilitados para resolver disputas.
+        #[ink(constructor)]
+        pub fn con_arbitros(arbitros: Vec<AccountId>) -> Self {
+            Self::con_personal(Vec::new(), arbitros)
+        }
+
+        /// Crea una nueva instancia vacía del marketplace con su personal inicial:
+        /// moderadores (que administran los permisos de los usuarios) y árbitros.
+        ///
+        /// Las cuentas quedan registradas solo con esos permisos; pueden sumar los
+        /// de compra y venta luego con `modificar_rol_usuario`. La cuenta que
+        /// despliega el contrato queda como propietaria.
+        #[ink(constructor)]
+        pub fn con_personal(moderadores: Vec<AccountId>, arbitros: Vec<AccountId>) -> Self {
+            let mut contrato = Self {
                 usuarios: Mapping::default(),
-                productos: Vec::new(),
-                ordenes: Vec::new(),
+                cuentas_registradas: Mapping::default(),
+                cantidad_usuarios: 0,
+                productos: Mapping::default(),
+                siguiente_id_producto: 0,
+                ordenes: Mapping::default(),
+                siguiente_id_orden: 0,
+                productos_por_vendedor: Mapping::default(),
+                cantidad_productos_por_vendedor: Mapping::default(),
+                ordenes_por_comprador: Mapping::default(),
+                cantidad_ordenes_por_comprador: Mapping::default(),
+                ordenes_por_vendedor: Mapping::default(),
+                cantidad_ordenes_por_vendedor: Mapping::default(),
+                propietario: Self::env().caller(),
+                administradores: Vec::new(),
+                pausado: false,
+                version_almacenamiento: VERSION_ALMACENAMIENTO,
+                comision_bps: Lazy::default(),
+                tesoreria: Lazy::default(),
+                saldos: Mapping::default(),
+                tokens_permitidos: Mapping::default(),
+                saldos_token: Mapping::default(),
+                tesoreria_token: Mapping::default(),
+                migracion: Lazy::default(),
+                permisos_bloqueados: Mapping::default(),
+            };
+            let personal = moderadores
+                .into_iter()
+                .map(|cuenta| (cuenta, Permiso::Moderar))
+                .chain(arbitros.into_iter().map(|cuenta| (cuenta, Permiso::Arbitrar)));
+            for (cuenta, permiso) in personal {
+                contrato.habilitar_personal(cuenta, permiso);
             }
+            contrato
         }
 
-        /// Registra un usuario con un rol específico (Comprador, Vendedor o Ambos).
-        ///
-        /// # Errores
-        /// - Retorna `UsuarioExistente` si la dirección ya está registrada.
+        /// Otorga un permiso de personal a una cuenta, registrándola solo con ese
+        /// permiso si todavía no estaba registrada.
+        fn habilitar_personal(&mut self, cuenta: AccountId, permiso: Permiso) {
+            let mut usuario = self.usuarios.get(cuenta).unwrap_or_else(|| {
+                self.indexar_usuario(cuenta);
+                Usuario::new(cuenta, Permisos::default())
+            });
+            usuario.permisos.otorgar(permiso);
+            self.usuarios.insert(cuenta, &usuario);
+        }
+
+        /// Consulta si una cuenta está habilitada como árbitro de disputas.
         #[ink(message)]
-        pub fn registrar_usuario(&mut self, rol: RolUsuario) -> Result<(), SistemaError> {
-            self.registrar_usuario_interno(rol)
+        pub fn es_arbitro(&self, cuenta: AccountId) -> bool {
+            self.tiene_permiso(cuenta, Permiso::Arbitrar)
         }
 
-        /// Consulta si un usuario está registrado en el sistema.
+        /// Consulta si una cuenta registrada tiene un permiso.
+        #[ink(message)]
+        pub fn tiene_permiso(&self, cuenta: AccountId, permiso: Permiso) -> bool {
+            self.verificar_permiso(cuenta, permiso).is_ok()
+        }
+
+        /// Permite a un moderador otorgar un permiso a un usuario registrado.
         ///
-        /// # Retorna
-        /// - `true` si el usuario está registrado.
-        /// - `false` si el usuario no está registrado.
+        /// Si el permiso había sido revocado, deja de estar bloqueado para `modificar_rol_usuario`.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller o la cuenta no están registrados.
+        /// - `NoEsRolCorrecto` si el caller no es moderador.
         #[ink(message)]
-        pub fn esta_registrado(&self, usuario: AccountId) -> bool {
-            self.usuarios.contains(&usuario)
+        pub fn otorgar_permiso(&mut self, cuenta: AccountId, permiso: Permiso) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.actualizar_permiso(cuenta, permiso, true)
         }
 
-        /// Obtiene la información de un usuario registrado.
+        /// Permite a un moderador revocar un permiso a un usuario registrado.
         ///
-        /// # Retorna
-        /// - `Some(Usuario)` si el usuario está registrado.
-        /// - `None` si el usuario no está registrado.
+        /// El permiso queda bloqueado: el usuario no puede recuperarlo con
+        /// `modificar_rol_usuario` hasta que un moderador vuelva a otorgarlo.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller o la cuenta no están registrados.
+        /// - `NoEsRolCorrecto` si el caller no es moderador.
         #[ink(message)]
-        pub fn obtener_usuario(&self, usuario: AccountId) -> Option<Usuario> {
-            self.usuarios.get(&usuario)
+        pub fn revocar_permiso(&mut self, cuenta: AccountId, permiso: Permiso) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.actualizar_permiso(cuenta, permiso, false)
         }
 
-        /// Lógica interna para registrar un usuario.
-        fn registrar_usuario_interno(&mut self, rol: RolUsuario) -> Result<(), SistemaError> {
-            let usuario_llamador = self.env().caller();
-            // Verifica si el usuario es existente
-            if self.usuarios.contains(&usuario_llamador) { // Cambia contains_key por contains
-                return Err(SistemaError::UsuarioExistente);
+        /// Lógica interna para otorgar o revocar un permiso.
+        fn actualizar_permiso(&mut self, cuenta: AccountId, permiso: Permiso, otorgado: bool) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_permiso(caller, Permiso::Moderar)?;
+            let mut usuario = self.usuarios.get(cuenta)
+                .ok_or(SistemaError::UsuarioNoRegistrado)?;
+            let mut bloqueados = self.permisos_bloqueados.get(cuenta).unwrap_or_default();
+            if otorgado {
+                usuario.permisos.otorgar(permiso);
+                bloqueados.revocar(permiso);
+            } else {
+                usuario.permisos.revocar(permiso);
+                bloqueados.otorgar(permiso);
             }
-            // Si no existe, crea un nuevo usuario
-            let nuevo_usuario = Usuario {
-                direccion: usuario_llamador,
-                rol,
-                reputacion_como_comprador: 0,
-                reputacion_como_vendedor: 0,
-            };
-            self.usuarios.insert(usuario_llamador, &nuevo_usuario);
+            self.usuarios.insert(cuenta, &usuario);
+            self.permisos_bloqueados.insert(cuenta, &bloqueados);
+            self.env().emit_event(PermisoActualizado {
+                usuario: cuenta,
+                permiso,
+                otorgado,
+                moderador: caller,
+            });
             Ok(())
         }
 
-        /// Modifica el rol de un usuario registrado.
-        /// # Errores
-        /// - `UsuarioNoRegistrado` si el usuario no está registrado.
-        /// - `NoEsRolCorrecto` si el usuario no puede cambiar a ese rol.
-        /// - `NoEsRolCorrecto` si el usuario ya está registrado con ese rol.
+        /// Cuenta propietaria del contrato.
         #[ink(message)]
-        pub fn modificar_rol_usuario(&mut self,nuevo_rol: RolUsuario,) -> Result<(), SistemaError> {
-            let usuario_llamador = self.env().caller();
-            // Verifica que el usuario esté registrado
-            self.verificar_registro(usuario_llamador)?;
-            
-            // Verifica que el usuario quiere cambiar a un rol que no es el actual
-            self.verificar_puede_cambiar_rol(usuario_llamador, nuevo_rol.clone())?;
+        pub fn propietario(&self) -> AccountId {
+            self.propietario
+        }
 
-            // Actualiza el rol del usuario
-            let mut usuario = self.usuarios.get(&usuario_llamador)
-                .ok_or(SistemaError::UsuarioNoRegistrado)?;
-            usuario.rol = nuevo_rol;
-            self.usuarios.insert(usuario_llamador, &usuario);
-            Ok(())
+        /// Consulta si una cuenta es administradora del contrato.
+        #[ink(message)]
+        pub fn es_administrador(&self, cuenta: AccountId) -> bool {
+            self.administradores.contains(&cuenta)
         }
 
-        fn modificar_rol_usuario_interno(&mut self,nuevo_rol: RolUsuario,) -> Result<(), SistemaError> {
-            let usuario_llamador = self.env().caller();
-            // Verifica que el usuario esté registrado
-            self.verificar_registro(usuario_llamador)?;
-            
-            // Verifica que el usuario quiere cambiar a un rol que no es el actual
-            self.verificar_puede_cambiar_rol(usuario_llamador, nuevo_rol.clone())?;
+        /// Consulta si el contrato está pausado.
+        #[ink(message)]
+        pub fn esta_pausado(&self) -> bool {
+            self.pausado
+        }
 
-            // Actualiza el rol del usuario
-            let mut usuario = self.usuarios.get(&usuario_llamador)
-                .ok_or(SistemaError::UsuarioNoRegistrado)?;
-            usuario.rol = nuevo_rol;
-            self.usuarios.insert(usuario_llamador, &usuario);
+        /// Permite al propietario transferir la propiedad del contrato a otra cuenta.
+        ///
+        /// # Errores
+        /// - `NoEsPropietario` si el caller no es el propietario.
+        #[ink(message)]
+        pub fn transferir_propiedad(&mut self, nuevo_propietario: AccountId) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_propietario(caller)?;
+            self.propietario = nuevo_propietario;
+            self.env().emit_event(PropiedadTransferida {
+                propietario_anterior: caller,
+                propietario_nuevo: nuevo_propietario,
+            });
             Ok(())
         }
 
-        /// Permite a un usuario con rol de Vendedor publicar un producto.
+        /// Permite al propietario habilitar una cuenta como administradora.
+        /// Si la cuenta ya era administradora no hace nada.
         ///
         /// # Errores
-        /// - `UsuarioNoRegistrado` si el usuario no está registrado.
-        /// - `NoEsRolCorrecto` si el usuario no es vendedor.
-        /// - `CantidadInsuficiente` si la cantidad es 0.
+        /// - `NoEsPropietario` si el caller no es el propietario.
         #[ink(message)]
-        pub fn publicar_producto(
-            &mut self,
-            nombre: String,
-            descripcion: String,
-            precio: Balance,
-            cantidad: u32,
-            categoria: String,
-        ) -> Result<(), SistemaError> {
-            self.crear_producto_seguro(nombre, descripcion, precio, cantidad, categoria)
+        pub fn agregar_administrador(&mut self, cuenta: AccountId) -> Result<(), SistemaError> {
+            self.verificar_propietario(self.env().caller())?;
+            if !self.administradores.contains(&cuenta) {
+                self.administradores.push(cuenta);
+                self.env().emit_event(AdministradorActualizado { cuenta, habilitado: true });
+            }
+            Ok(())
         }
 
-        /// Lógica interna para validar y agregar un producto.
-        fn crear_producto_seguro(
-            &mut self,
-            nombre: String,
-            descripcion: String,
-            precio: Balance,
-            cantidad: u32,
-            categoria: String,
-        ) -> Result<(), SistemaError> {
-            let vendedor = self.env().caller();
-            // Verifica que el vendedor esté registrado y tenga el rol adecuado
-            self.verificar_registro(vendedor)?;
-            self.verificar_rol(vendedor, RolUsuario::Vendedor)?;
-            // Verifica que la cantidad sea válida
-            self.verificar_cantidad(cantidad)?;
-            // Agrega el producto al marketplace
-            self.agregar_producto(nombre, descripcion, precio, cantidad, categoria, vendedor)
+        /// Permite al propietario quitar una cuenta de los administradores.
+        /// Si la cuenta no era administradora no hace nada.
+        ///
+        /// # Errores
+        /// - `NoEsPropietario` si el caller no es el propietario.
+        #[ink(message)]
+        pub fn quitar_administrador(&mut self, cuenta: AccountId) -> Result<(), SistemaError> {
+            self.verificar_propietario(self.env().caller())?;
+            if let Some(posicion) = self.administradores.iter().position(|a| *a == cuenta) {
+                self.administradores.swap_remove(posicion);
+                self.env().emit_event(AdministradorActualizado { cuenta, habilitado: false });
+            }
+            Ok(())
         }
 
-        /// Permite a un usuario con rol de Comprador crear una orden de compra.
+        /// Permite al propietario o a un administrador habilitar una cuenta como árbitro
+        /// de disputas. Si la cuenta no está registrada queda registrada solo con ese
+        /// permiso, como el personal de `con_personal`.
+        ///
+        /// Así un contrato creado con `new`, sin árbitros ni moderadores, puede
+        /// resolver las disputas que se abran.
         ///
         /// # Errores
-        /// - `UsuarioNoRegistrado` si el usuario no está registrado.
-        /// - `NoEsRolCorrecto` si el usuario no es comprador.
-        /// - `ProductosVacios` si el producto no existe.
-        /// - `CantidadInsuficiente` si la cantidad solicitada es 0.
-        /// - `StockInsuficiente` si no hay suficiente stock disponible.
-        /// 
-        /// # Nota
-        /// Esta función requiere que el usuario esté previamente registrado como Comprador o Ambos.
-        /// Si no está registrado, debe llamar primero a `registrar_usuario()`.
-        /// La función verifica el stock disponible antes de crear la orden y descuenta automáticamente
-        /// el stock del producto una vez confirmada la compra.
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `NoEsAdministrador` si el caller no es el propietario ni un administrador.
         #[ink(message)]
-        pub fn crear_orden(&mut self, producto_id: u32, cantidad: u32) -> Result<u32, SistemaError> {
-            self.crear_nueva_orden(producto_id, cantidad)
-        }
-        
-        /// Lógica interna para crear una nueva orden de compra.
-        fn crear_nueva_orden(&mut self, producto_id: u32, cantidad: u32) -> Result<u32, SistemaError> {
-            let comprador = self.env().caller();
-            
-            // Validación temprana: verificar registro antes de cualquier otra operación
-            self.verificar_registro(comprador)?;
-            self.verificar_puede_comprar(comprador)?;
-            
-            // Validar que la cantidad solicitada sea válida
-            self.verificar_cantidad(cantidad)?;
-            
-            // Obtén el vendedor antes del mutable borrow
-            let vendedor = {
-                let producto_ref = self.productos.iter().find(|p| p.id == producto_id)
-                    .ok_or(SistemaError::ProductosVacios)?;
-                
-                // Verificar stock disponible antes de proceder
-                self.verificar_stock_disponible(producto_ref, cantidad)?;
-                
-                producto_ref.vendedor
-            };
-            
-            // Ahora sí obtener el producto mutable y descontar stock
-            let producto = self.obtener_producto_mut(producto_id)?;
-            producto.cantidad = producto.cantidad.saturating_sub(cantidad);
-            
-            self.crear_y_emitir_orden(comprador, vendedor, producto_id, cantidad)
+        pub fn designar_arbitro(&mut self, cuenta: AccountId) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            let caller = self.env().caller();
+            self.verificar_administrador(caller)?;
+            self.habilitar_personal(cuenta, Permiso::Arbitrar);
+            self.env().emit_event(PermisoActualizado {
+                usuario: cuenta,
+                permiso: Permiso::Arbitrar,
+                otorgado: true,
+                moderador: caller,
+            });
+            Ok(())
         }
 
-        /// Permite al vendedor marcar una orden como enviada.
+        /// Pausa el contrato: los mensajes que modifican el estado, incluidos la
+        /// comisión, los tokens permitidos y el retiro de comisiones, fallan con
+        /// `ContratoPausado` hasta que se llame a `reanudar`. Siguen disponibles las
+        /// consultas, `actualizar_codigo` y la gestión de la propiedad y de los
+        /// administradores.
         ///
         /// # Errores
-        /// - `NoEsRolCorrecto` si el caller no es el vendedor de la orden.
-        /// - `EstadoInvalido` si la transición de estado no es válida.
+        /// - `NoEsAdministrador` si el caller no es el propietario ni un administrador.
+        /// - `ContratoPausado` si el contrato ya está pausado.
         #[ink(message)]
-        pub fn marcar_orden_como_enviada(&mut self, orden_id: u32) -> Result<(), SistemaError> {
-            self.actualizar_estado_orden(orden_id, EstadoOrden::Enviada)
+        pub fn pausar(&mut self) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_administrador(caller)?;
+            self.verificar_no_pausado()?;
+            self.pausado = true;
+            self.env().emit_event(Pausado { cuenta: caller });
+            Ok(())
         }
 
-        /// Permite al comprador marcar una orden como recibida.
+        /// Reanuda un contrato pausado.
         ///
         /// # Errores
-        /// - `NoEsRolCorrecto` si el caller no es el comprador de la orden.
-        /// - `EstadoInvalido` si la transición de estado no es válida.
+        /// - `NoEsAdministrador` si el caller no es el propietario ni un administrador.
+        /// - `ContratoNoPausado` si el contrato no está pausado.
         #[ink(message)]
-        pub fn marcar_como_recibida(&mut self, orden_id: u32) -> Result<(), SistemaError> {
-            self.actualizar_estado_orden(orden_id, EstadoOrden::Recibida)
+        pub fn reanudar(&mut self) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_administrador(caller)?;
+            if !self.pausado {
+                return Err(SistemaError::ContratoNoPausado);
+            }
+            self.pausado = false;
+            self.env().emit_event(Reanudado { cuenta: caller });
+            Ok(())
         }
 
-        /// Lógica interna para actualizar el estado de una orden.
-        fn actualizar_estado_orden(&mut self, orden_id: u32, nuevo_estado: EstadoOrden) -> Result<(), SistemaError> {
-            let caller = self.env().caller();
-            self.verificar_registro(caller)?;
-            // Primero obten la orden de forma inmutable para verificar el permiso
-            {
-                let orden_ref = self.ordenes.get(orden_id as usize).ok_or(SistemaError::OrdenNoExiste)?;
-                self.verificar_permiso_orden(caller, orden_ref, &nuevo_estado)?;
-            }
-            // Luego pide el borrow mutable para modificar el estado
-            let orden = self.obtener_orden_mut(orden_id)?;
-            let _estado_anterior = orden.estado.clone();
-            orden.estado = nuevo_estado;
+        /// Versión del formato con el que están guardados los registros.
+        ///
+        /// Si es menor que `VERSION_ALMACENAMIENTO`, el código fue actualizado y
+        /// falta ejecutar `migrar`.
+        #[ink(message)]
+        pub fn version_almacenamiento(&self) -> u32 {
+            self.version_almacenamiento
+        }
+
+        /// Permite al propietario reemplazar el código del contrato conservando su estado.
+        ///
+        /// El código nuevo se usa a partir de la próxima llamada. Si cambia el formato
+        /// de los registros, los mensajes que modifican el estado quedan bloqueados
+        /// con `MigracionPendiente` hasta que el propietario llame a `migrar`.
+        /// Puede usarse con el contrato pausado.
+        ///
+        /// # Errores
+        /// - `NoEsPropietario` si el caller no es el propietario.
+        /// - `CodigoInvalido` si no hay código subido con ese hash.
+        #[ink(message)]
+        pub fn actualizar_codigo(&mut self, code_hash: Hash) -> Result<(), SistemaError> {
+            self.verificar_propietario(self.env().caller())?;
+            self.env()
+                .set_code_hash(&code_hash)
+                .map_err(|_| SistemaError::CodigoInvalido)?;
+            self.env().emit_event(CodigoActualizado { code_hash });
             Ok(())
         }
 
+        /// Permite al propietario convertir los registros guardados con una versión
+        /// anterior al formato de `VERSION_ALMACENAMIENTO`.
+        ///
+        /// Convierte como máximo `limite` registros por llamada, para no superar el
+        /// peso de un bloque en un marketplace grande, y guarda el avance para seguir
+        /// en la próxima llamada. La versión del almacenamiento solo se actualiza
+        /// (y los mensajes se desbloquean) cuando termina el último lote.
+        ///
+        /// # Retorna
+        /// - `true` si la migración terminó.
+        /// - `false` si quedan registros y hay que volver a llamar a `migrar`.
+        ///
+        /// # Errores
+        /// - `NoEsPropietario` si el caller no es el propietario.
+        /// - `SinMigracionPendiente` si el almacenamiento ya está en la versión actual.
+        #[ink(message)]
+        pub fn migrar(&mut self, limite: u32) -> Result<bool, SistemaError> {
+            self.migrar_interno(limite)
+        }
 
-        // --- Funciones auxiliares ---
+        /// Avance de la migración pendiente, o `None` si el almacenamiento ya está
+        /// en la versión actual.
+        #[ink(message)]
+        pub fn progreso_migracion(&self) -> Option<ProgresoMigracion> {
+            (self.version_almacenamiento < VERSION_ALMACENAMIENTO).then(|| self.progreso_actual())
+        }
 
-        /// Verifica si un usuario está registrado.
-        fn verificar_registro(&self, usuario: AccountId) -> Result<(), SistemaError> {
-            if !self.usuarios.contains(&usuario) { // Cambia contains_key por contains
-                Err(SistemaError::UsuarioNoRegistrado)
-            } else {
-                Ok(())
+        /// Lógica interna para migrar un lote de registros hacia la versión actual.
+        fn migrar_interno(&mut self, limite: u32) -> Result<bool, SistemaError> {
+            self.verificar_propietario(self.env().caller())?;
+            let version_anterior = self.version_almacenamiento;
+            if version_anterior >= VERSION_ALMACENAMIENTO {
+                return Err(SistemaError::SinMigracionPendiente);
+            }
+            let mut progreso = self.progreso_actual();
+            let mut restantes = limite;
+            while progreso.paso <= VERSION_ALMACENAMIENTO {
+                let total = self.registros_del_paso(progreso.paso);
+                if progreso.posicion >= total {
+                    progreso = ProgresoMigracion { paso: progreso.paso + 1, posicion: 0 };
+                    continue;
+                }
+                if restantes == 0 {
+                    break;
+                }
+                let fin = progreso.posicion.saturating_add(restantes).min(total);
+                for posicion in progreso.posicion..fin {
+                    self.migrar_registro(progreso.paso, posicion);
+                }
+                restantes -= fin - progreso.posicion;
+                progreso.posicion = fin;
+            }
+            self.migracion.set(&progreso);
+            if progreso.paso <= VERSION_ALMACENAMIENTO {
+                return Ok(false);
             }
+            self.version_almacenamiento = VERSION_ALMACENAMIENTO;
+            self.env().emit_event(AlmacenamientoMigrado {
+                version_anterior,
+                version_nueva: VERSION_ALMACENAMIENTO,
+            });
+            Ok(true)
         }
 
-        /// Verifica si el usuario tiene el rol requerido.
-        fn verificar_rol(&self, usuario: AccountId, rol_requerido: RolUsuario) -> Result<(), SistemaError> {
-            let usuario_data = self.usuarios.get(&usuario)
-                .ok_or(SistemaError::UsuarioNoRegistrado)?;
-
-            match (usuario_data.rol, rol_requerido) {
-                // Solo usuarios con rol Comprador pueden crear órdenes
-                (RolUsuario::Comprador, RolUsuario::Comprador) => Ok(()),
-                // Solo usuarios con rol Vendedor pueden publicar productos
-                (RolUsuario::Vendedor, RolUsuario::Vendedor) => Ok(()),
-                // Usuarios con rol Ambos pueden hacer ambas acciones
-                (RolUsuario::Ambos, _) => Ok(()),
-                _ => Err(SistemaError::NoEsRolCorrecto),
+        /// Avance guardado de la migración, o el comienzo del primer paso pendiente
+        /// si todavía no se migró ningún lote desde la versión actual.
+        fn progreso_actual(&self) -> ProgresoMigracion {
+            match self.migracion.get() {
+                Some(progreso) if progreso.paso > self.version_almacenamiento => progreso,
+                _ => ProgresoMigracion { paso: self.version_almacenamiento + 1, posicion: 0 },
             }
         }
 
-        fn verificar_puede_cambiar_rol(&self, usuario:AccountId, rol_solicitado: RolUsuario) -> Result<(), SistemaError> {
-            let usuario_data = self.usuarios.get(&usuario)
-                .ok_or(SistemaError::UsuarioNoRegistrado)?;
+        /// Cantidad de posiciones que recorre el paso que lleva a la versión `paso`.
+        ///
+        /// Cada versión nueva del formato agrega acá y en `migrar_registro` el paso
+        /// que reescribe los registros guardados con la versión anterior. Los pasos
+        /// que convierten productos y órdenes recorren primero los productos.
+        fn registros_del_paso(&self, paso: u32) -> u32 {
+            match paso {
+                2 => self.siguiente_id_producto.saturating_add(self.siguiente_id_orden),
+                3 | 4 | 6 => self.siguiente_id_orden,
+                5 => self
+                    .cantidad_usuarios
+                    .saturating_add(self.siguiente_id_producto)
+                    .saturating_add(self.siguiente_id_orden),
+                _ => 0,
+            }
+        }
 
-            match (usuario_data.rol, rol_solicitado) {
-                // Solo usuarios con rol Vendedor pueden cambiar a Comprador
-                (RolUsuario::Vendedor, RolUsuario::Comprador) => Ok(()),
-                // Solo usuarios con rol Comprador pueden cambiar a Vendedor
-                (RolUsuario::Comprador, RolUsuario::Vendedor) => Ok(()),
-                // Usuarios con rol Ambos pueden cambiar a cualquier rol
-                (RolUsuario::Ambos, _) => Ok(()),
-                _ => Err(SistemaError::NoEsRolCorrecto),
+        /// Convierte el registro de una posición del paso que lleva a la versión `paso`.
+        fn migrar_registro(&mut self, paso: u32, posicion: u32) {
+            match paso {
+                2 => self.migrar_a_v2(posicion),
+                3 => self.migrar_a_v3(posicion),
+                4 => self.migrar_a_v4(posicion),
+                5 => self.migrar_a_v5(posicion),
+                6 => self.migrar_a_v6(posicion),
+                _ => {}
             }
         }
 
-        /// Verifica específicamente si el usuario puede crear órdenes.
-        /// Solo usuarios con rol Comprador o Ambos pueden crear órdenes.
-        /// Los usuarios con rol Vendedor no pueden crear órdenes.
-        fn verificar_puede_comprar(&self, usuario: AccountId) -> Result<(), SistemaError> {
-            let usuario_data = self.usuarios.get(&usuario)
-                .ok_or(SistemaError::UsuarioNoRegistrado)?;
+        /// Comisión vigente del marketplace, en puntos básicos (100 = 1%).
+        #[ink(message)]
+        pub fn comision_bps(&self) -> u16 {
+            self.comision_bps.get().unwrap_or_default()
+        }
 
-            match usuario_data.rol {
-                RolUsuario::Comprador | RolUsuario::Ambos => Ok(()),
-                RolUsuario::Vendedor => Err(SistemaError::NoEsRolCorrecto),
-            }
+        /// Comisiones cobradas que el propietario todavía no retiró.
+        #[ink(message)]
+        pub fn tesoreria(&self) -> Balance {
+            self.tesoreria.get().unwrap_or_default()
         }
 
-        /// Verifica que la cantidad sea mayor a cero.
-        fn verificar_cantidad(&self, cantidad: u32) -> Result<(), SistemaError> {
-            if cantidad == 0 {
-                Err(SistemaError::CantidadInsuficiente)
-            } else {
-                Ok(())
+        /// Permite al propietario fijar la comisión que se descuenta de cada pago
+        /// liquidado al vendedor. Se aplica a las órdenes que se liquiden a partir
+        /// de este momento.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `NoEsPropietario` si el caller no es el propietario.
+        /// - `ComisionInvalida` si supera `COMISION_MAXIMA_BPS`.
+        #[ink(message)]
+        pub fn fijar_comision(&mut self, comision_bps: u16) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.verificar_propietario(self.env().caller())?;
+            if comision_bps > COMISION_MAXIMA_BPS {
+                return Err(SistemaError::ComisionInvalida);
             }
+            self.comision_bps.set(&comision_bps);
+            self.env().emit_event(ComisionActualizada { comision_bps });
+            Ok(())
         }
 
-        /// Verifica que hay suficiente stock disponible para la cantidad solicitada.
-        fn verificar_stock_disponible(&self, producto: &Producto, cantidad_solicitada: u32) -> Result<(), SistemaError> {
-            if producto.cantidad < cantidad_solicitada {
-                Err(SistemaError::StockInsuficiente)
-            } else {
-                Ok(())
+        /// Comisiones cobradas en una moneda que el propietario todavía no retiró.
+        #[ink(message)]
+        pub fn tesoreria_en_moneda(&self, moneda: Moneda) -> Balance {
+            match moneda {
+                Moneda::Nativa => self.tesoreria(),
+                Moneda::Psp22(token) => self.tesoreria_token.get(token).unwrap_or_default(),
             }
         }
 
-        /// Agrega un producto a la lista de productos.
-        fn agregar_producto(
-            &mut self,
-            nombre: String,
-            descripcion: String,
-            precio: Balance,
-            cantidad: u32,
-            categoria: String,
-            vendedor: AccountId,
-        ) -> Result<(), SistemaError> {
-            let id = self.productos.len() as u32;
-            let nuevo_producto = Producto::new(id, nombre, descripcion, precio, cantidad, categoria, vendedor);
-            self.productos.push(nuevo_producto);
-            Ok(())
+        /// Permite al propietario retirar comisiones acumuladas en la tesorería.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `NoEsPropietario` si el caller no es el propietario.
+        /// - `ComisionesInsuficientes` si el monto supera lo acumulado en la tesorería.
+        /// - `TransferenciaFallida` si no se pudo transferir el monto.
+        #[ink(message)]
+        pub fn retirar_comisiones(&mut self, destino: AccountId, monto: Balance) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.retirar_comisiones_interno(Moneda::Nativa, destino, monto)
         }
-        /// Obtiene un producto mutable por su id.
-        fn obtener_producto_mut(&mut self, id: u32) -> Result<&mut Producto, SistemaError> {
-            self.productos
-                .iter_mut()
-                .find(|p| p.id == id)
-                .ok_or(SistemaError::ProductosVacios)
+
+        /// Permite al propietario retirar comisiones cobradas en un token.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `NoEsPropietario` si el caller no es el propietario.
+        /// - `ComisionesInsuficientes` si el monto supera lo acumulado en esa moneda.
+        /// - `TransferenciaFallida` si no se pudo transferir el monto.
+        #[ink(message)]
+        pub fn retirar_comisiones_en_moneda(&mut self, moneda: Moneda, destino: AccountId, monto: Balance) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.retirar_comisiones_interno(moneda, destino, monto)
         }
 
-        /// Crea y almacena una nueva orden.
-        fn crear_y_emitir_orden(
-            &mut self,
-            comprador: AccountId,
-            vendedor: AccountId,
-            producto_id: u32,
-            cantidad: u32
-        ) -> Result<u32, SistemaError> {
-            let id = self.ordenes.len() as u32;
-            let nueva_orden = Orden::new(id, comprador, vendedor, producto_id, cantidad);
-            self.ordenes.push(nueva_orden.clone());
-            // self.emitir_evento_creacion(nueva_orden);
-            Ok(id)
+        /// Lógica interna para retirar comisiones de la tesorería de una moneda.
+        fn retirar_comisiones_interno(&mut self, moneda: Moneda, destino: AccountId, monto: Balance) -> Result<(), SistemaError> {
+            self.verificar_propietario(self.env().caller())?;
+            let disponible = self.tesoreria_en_moneda(moneda);
+            if monto > disponible {
+                return Err(SistemaError::ComisionesInsuficientes);
+            }
+            self.fijar_tesoreria(moneda, disponible - monto);
+            self.transferir_fondos(moneda, destino, monto)?;
+            self.env().emit_event(ComisionesRetiradas { destino, moneda, monto });
+            Ok(())
         }
 
-        /// Obtiene una orden mutable por su id.
-        fn obtener_orden_mut(&mut self, id: u32) -> Result<&mut Orden, SistemaError> {
-            self.ordenes
-                .get_mut(id as usize)
-                .ok_or(SistemaError::OrdenNoExiste)
+        /// Saldo que una cuenta tiene disponible para retirar con `retirar_fondos`.
+        #[ink(message)]
+        pub fn saldo_de(&self, cuenta: AccountId) -> Balance {
+            self.saldos.get(cuenta).unwrap_or_default()
         }
-        /// Verifica si el caller tiene permiso para cambiar el estado de la orden.
-        fn verificar_permiso_orden(
-            &self,
-            caller: AccountId,
-            orden: &Orden,
-            nuevo_estado: &EstadoOrden
-        ) -> Result<(), SistemaError> {
-            match nuevo_estado {
-                EstadoOrden::Enviada if caller != orden.vendedor => Err(SistemaError::NoEsRolCorrecto),
-                EstadoOrden::Recibida if caller != orden.comprador => Err(SistemaError::NoEsRolCorrecto),
-                _ => self.verificar_transicion_estado(&orden.estado, nuevo_estado),
+
+        /// Saldo en una moneda que una cuenta tiene disponible para retirar.
+        #[ink(message)]
+        pub fn saldo_en_moneda(&self, cuenta: AccountId, moneda: Moneda) -> Balance {
+            match moneda {
+                Moneda::Nativa => self.saldo_de(cuenta),
+                Moneda::Psp22(token) => self.saldos_token.get((cuenta, token)).unwrap_or_default(),
             }
         }
 
-        /// Verifica que la transición de estado de la orden sea válida.
-        fn verificar_transicion_estado(
-            &self,
-            actual: &EstadoOrden,
-            nuevo: &EstadoOrden
-        ) -> Result<(), SistemaError> {
-            match (actual, nuevo) {
-                (EstadoOrden::Pendiente, EstadoOrden::Enviada) => Ok(()),
-                (EstadoOrden::Enviada, EstadoOrden::Recibida) => Ok(()),
-                _ => Err(SistemaError::EstadoInvalido),
-            }
+        /// Permite a una cuenta retirar parte o todo su saldo: lo cobrado por ventas y
+        /// los reembolsos de órdenes canceladas o disputas resueltas a su favor.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `SaldoInsuficiente` si el monto supera el saldo disponible.
+        /// - `TransferenciaFallida` si no se pudo transferir el monto.
+        #[ink(message)]
+        pub fn retirar_fondos(&mut self, monto: Balance) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.retirar_fondos_interno(Moneda::Nativa, monto)
         }
-    }
 
-    // ────────────────
-    // ENUMS
-    // ────────────────
+        /// Permite a una cuenta retirar su saldo en una moneda, con el mismo criterio
+        /// que `retirar_fondos`.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `SaldoInsuficiente` si el monto supera el saldo disponible en esa moneda.
+        /// - `TransferenciaFallida` si no se pudo transferir el monto.
+        #[ink(message)]
+        pub fn retirar_fondos_en_moneda(&mut self, moneda: Moneda, monto: Balance) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.retirar_fondos_interno(moneda, monto)
+        }
 
-    /// Enum para los roles posibles de un usuario.
-    #[derive(Debug, Clone, PartialEq, Eq)]
-    #[ink::scale_derive(Encode, Decode, TypeInfo)]
-    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
-    pub enum RolUsuario {
-        Comprador,
-        Vendedor,
-        Ambos,
-    }
+        /// Lógica interna para retirar fondos del saldo del caller.
+        fn retirar_fondos_interno(&mut self, moneda: Moneda, monto: Balance) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            let saldo = self.saldo_en_moneda(caller, moneda);
+            if monto > saldo {
+                return Err(SistemaError::SaldoInsuficiente);
+            }
+            // Descuenta el saldo antes de transferir, así una reentrada no puede retirarlo dos veces
+            self.fijar_saldo(caller, moneda, saldo - monto);
+            self.transferir_fondos(moneda, caller, monto)?;
+            self.env().emit_event(FondosRetirados { cuenta: caller, moneda, monto });
+            Ok(())
+        }
 
-    /// Enum para los posibles estados de una orden.
-#[derive(Debug, Clone, PartialEq, Eq)]
-    #[ink::scale_derive(Encode, Decode, TypeInfo)]
-    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]    pub enum EstadoOrden {
-        Pendiente,
-        Enviada,
-        Recibida,
-        Cancelada,
-    }
+        /// Consulta si se pueden publicar productos con precio en un token.
+        #[ink(message)]
+        pub fn es_token_permitido(&self, token: AccountId) -> bool {
+            self.tokens_permitidos.contains(token)
+        }
 
-    // ────────────────
-    // ERRORES DEL SISTEMA
-    // ────────────────
+        /// Permite al propietario habilitar un token PSP22 como moneda de los productos.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `NoEsPropietario` si el caller no es el propietario.
+        #[ink(message)]
+        pub fn permitir_token(&mut self, token: AccountId) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.verificar_propietario(self.env().caller())?;
+            self.tokens_permitidos.insert(token, &());
+            self.env().emit_event(TokenActualizado { token, permitido: true });
+            Ok(())
+        }
 
-    /// Enum para los posibles errores del sistema.
-#[derive(Debug, Clone, PartialEq, Eq)]
-    #[ink::scale_derive(Encode, Decode, TypeInfo)]
-    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]    pub enum SistemaError {
-        CantidadInsuficiente,
-        UsuarioNoRegistrado,
-        ProductosVacios,
-        NoEsRolCorrecto,
-        EstadoInvalido,
-        OrdenNoExiste,
-        UsuarioExistente,
-        StockInsuficiente,
-    }
-    impl core::fmt::Display for SistemaError {
-        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-            match self {
-                SistemaError::CantidadInsuficiente => write!(f, "Cantidad insuficiente"),
-                SistemaError::UsuarioNoRegistrado => write!(f, "El usuario no se encuentra registrado"),
-                SistemaError::NoEsRolCorrecto => write!(f, "El usuario no es del rol correcto"),
-                SistemaError::ProductosVacios => write!(f, "No se encontraron productos"),
-                SistemaError::EstadoInvalido => write!(f, "El estado de la orden es inválido"),
-                SistemaError::OrdenNoExiste => write!(f, "La orden no existe"),
-                SistemaError::UsuarioExistente => write!(f, "El usuario ya está registrado"),
-                SistemaError::StockInsuficiente => write!(f, "Stock insuficiente para la cantidad solicitada"),
+        /// Permite al propietario quitar un token de la lista de permitidos. Los
+        /// productos publicados en ese token dejan de aceptar órdenes, pero las
+        /// órdenes ya creadas se liquidan normalmente.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `NoEsPropietario` si el caller no es el propietario.
+        #[ink(message)]
+        pub fn quitar_token(&mut self, token: AccountId) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.verificar_propietario(self.env().caller())?;
+            self.tokens_permitidos.remove(token);
+            self.env().emit_event(TokenActualizado { token, permitido: false });
+            Ok(())
+        }
+
+        /// Versión 1 → 2: `Producto` y `Orden` suman la moneda, que es la nativa
+        /// para todos los registros anteriores.
+        fn migrar_a_v2(&mut self, posicion: u32) {
+            if posicion < self.siguiente_id_producto {
+                if let Some(anterior) = Self::leer_registro_anterior::<v1::Producto, _, _>(&self.productos, posicion) {
+                    self.productos.insert(posicion, &Producto::from(anterior));
+                }
+                return;
+            }
+            // Las órdenes quedan en el formato de la versión 2 para que las convierta `migrar_a_v3`
+            let id = posicion - self.siguiente_id_producto;
+            if let Some(anterior) = Self::leer_registro_anterior::<v1::Orden, _, _>(&self.ordenes, id) {
+                Self::escribir_registro_anterior(&self.ordenes, id, &v2::Orden::from(anterior));
             }
         }
-    }
 
-    // ────────────────
-    // ESTRUCTURAS PRINCIPALES
-    // ────────────────
+        /// Versión 2 → 3: `Orden` pasa de un único producto a una lista de líneas,
+        /// para admitir órdenes de varios productos del mismo vendedor.
+        fn migrar_a_v3(&mut self, id: u32) {
+            if let Some(anterior) = Self::leer_registro_anterior::<v2::Orden, _, _>(&self.ordenes, id) {
+                Self::escribir_registro_anterior(&self.ordenes, id, &v3::Orden::from(anterior));
+            }
+        }
 
-    /// Representa un usuario del marketplace.
-    
-#[derive(Debug, Clone, PartialEq, Eq)]
-    #[ink::scale_derive(Encode, Decode, TypeInfo)]
-    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]    pub struct Usuario {
-        /// Dirección de la cuenta del usuario.
-        pub direccion: AccountId,
-        /// Rol asignado al usuario.
-        pub rol: RolUsuario,
-        /// Reputación como comprador.
-        pub reputacion_como_comprador: u32,
-        /// Reputación como vendedor.
-        pub reputacion_como_vendedor: u32,
-    }
+        /// Versión 3 → 4: `Orden` registra cuándo se creó y cuándo se envió. Como
+        /// esos momentos se desconocen para las órdenes existentes, sus plazos
+        /// empiezan a correr desde la migración.
+        fn migrar_a_v4(&mut self, id: u32) {
+            let ahora = self.env().block_timestamp();
+            if let Some(anterior) = Self::leer_registro_anterior::<v3::Orden, _, _>(&self.ordenes, id) {
+                let mut orden = v5::Orden::from(anterior);
+                orden.creada_en = ahora;
+                if orden.estado == EstadoOrden::Enviada {
+                    orden.enviada_en = Some(ahora);
+                }
+                // Las órdenes quedan en el formato de la versión 5 para que las convierta `migrar_a_v6`
+                Self::escribir_registro_anterior(&self.ordenes, id, &orden);
+            }
+        }
 
-    /// Representa un producto publicado en el marketplace.
-#[derive(Debug, Clone, PartialEq, Eq)]
-    #[ink::scale_derive(Encode, Decode, TypeInfo)]
-    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]    pub struct Producto {
-        /// Identificador único del producto.
-        pub id: u32,
-        /// Nombre del producto.
-        pub nombre: String,
-        /// Descripción del producto.
-        pub descripcion: String,
-        /// Precio del producto.
-        pub precio: Balance,
-        /// Cantidad disponible.
-        pub cantidad: u32,
-        /// Categoría del producto.
-        pub categoria: String,
-        /// Dirección del vendedor.
-        pub vendedor: AccountId,
-    }
-    impl Producto {
-        /// Crea una nueva instancia de Producto.
-        pub fn new(id: u32, nombre: String, descripcion: String, precio: Balance, cantidad: u32, categoria: String, vendedor: AccountId) -> Self {
-            Self {
-                id,
-                nombre,
-                descripcion,
-                precio,
-                cantidad,
-                categoria,
-                vendedor,
+        /// Versión 4 → 5: los índices por cuenta pasan de una lista de ids por cuenta,
+        /// que crecía sin límite, a una entrada por (cuenta, posición) con un contador.
+        ///
+        /// Recorre primero los usuarios registrados, vaciando sus índices y
+        /// descartando las listas anteriores, y después vuelve a indexar los
+        /// productos y las órdenes en orden de id.
+        fn migrar_a_v5(&mut self, posicion: u32) {
+            if posicion < self.cantidad_usuarios {
+                if let Some(cuenta) = self.cuentas_registradas.get(posicion) {
+                    self.vaciar_indices(cuenta);
+                }
+                return;
+            }
+            let posicion = posicion - self.cantidad_usuarios;
+            if posicion < self.siguiente_id_producto {
+                if let Some(producto) = self.productos.get(posicion) {
+                    self.indexar_producto(producto.vendedor, posicion);
+                }
+                return;
+            }
+            let id = posicion - self.siguiente_id_producto;
+            if let Some(orden) = Self::leer_registro_anterior::<v5::Orden, _, _>(&self.ordenes, id) {
+                self.indexar_orden(orden.comprador, orden.vendedor, id);
             }
         }
-    }
 
-    /// Representa una orden de compra.
-    #[derive(Debug, Clone, PartialEq, Eq)]
-    #[ink::scale_derive(Encode, Decode, TypeInfo)]
-    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]    
-    pub struct Orden {
-        /// Identificador único de la orden.
-        pub id: u32,
-        /// Dirección del comprador.
-        pub comprador: AccountId,
-        /// Dirección del vendedor.
-        pub vendedor: AccountId,
-        /// Identificador del producto comprado.
-        pub producto_id: u32,
-        /// Cantidad comprada.
-        pub cantidad: u32,
-        /// Estado actual de la orden.
-        pub estado: EstadoOrden,
-        /// Indica si el comprador calificó.
-        pub comprador_califico: bool,
-        /// Indica si el vendedor calificó.
-        pub vendedor_califico: bool,
-    }
-    impl Orden {
-        /// Crea una nueva instancia de Orden.
-        pub fn new(id: u32, comprador: AccountId, vendedor: AccountId, producto_id: u32, cantidad: u32) -> Self {
-            Self {
-                id,
-                comprador,
-                vendedor,
-                producto_id,
-                cantidad,
-                estado: EstadoOrden::Pendiente,
-                comprador_califico: false,
-                vendedor_califico: false,
+        /// Versión 5 → 6: `Orden` registra la comisión descontada al liquidarla. La
+        /// tasa vigente al liquidar las órdenes existentes no se guardó, así que
+        /// quedan con comisión 0.
+        fn migrar_a_v6(&mut self, id: u32) {
+            if let Some(anterior) = Self::leer_registro_anterior::<v5::Orden, _, _>(&self.ordenes, id) {
+                self.ordenes.insert(id, &Orden::from(anterior));
             }
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        use ink::env::test;
+        /// Vacía los índices de una cuenta y borra las listas de ids con las que
+        /// se guardaban hasta la versión 4, bajo la misma clave de cada índice.
+        fn vaciar_indices(&mut self, cuenta: AccountId) {
+            self.cantidad_productos_por_vendedor.remove(cuenta);
+            self.cantidad_ordenes_por_comprador.remove(cuenta);
+            self.cantidad_ordenes_por_vendedor.remove(cuenta);
+            Self::borrar_registro_anterior(&self.productos_por_vendedor, cuenta);
+            Self::borrar_registro_anterior(&self.ordenes_por_comprador, cuenta);
+            Self::borrar_registro_anterior(&self.ordenes_por_vendedor, cuenta);
+        }
 
-        // Función auxiliar para crear un contrato con un vendedor registrado y caller seteado
-        fn setup_contract_con_vendedor() -> MarketplacePrincipal {
-            let mut contrato = MarketplacePrincipal::new();
-            let caller = AccountId::from([0x01; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(caller);
-            let usuario = Usuario {
-                direccion: caller,
-                rol: RolUsuario::Vendedor,
-                reputacion_como_comprador: 0,
-                reputacion_como_vendedor: 0,
-            };
-            contrato.usuarios.insert(caller, &usuario);
-            contrato
+        /// Lee un registro de un `Mapping` con el formato de una versión anterior,
+        /// o `None` si no existe.
+        ///
+        /// La decodificación no detecta si el registro ya está en un formato más nuevo:
+        /// solo es correcta mientras siga en el formato pedido. `migrar` lo garantiza
+        /// convirtiendo cada registro una sola vez por paso, con los mensajes que
+        /// crean registros bloqueados hasta que termine.
+        fn leer_registro_anterior<T: ink::storage::traits::Storable, V: Packed, K: StorageKey>(_registros: &Mapping<u32, V, K>, id: u32) -> Option<T> {
+            ink::env::get_contract_storage::<(u32, u32), T>(&(K::KEY, id)).ok().flatten()
         }
-        
-        // --- Registro de usuarios ---
+
+        /// Escribe un registro en un `Mapping` con el formato de una versión anterior,
+        /// como paso intermedio de una migración de varias versiones.
+        fn escribir_registro_anterior<T: ink::storage::traits::Storable, V: Packed, K: StorageKey>(_registros: &Mapping<u32, V, K>, id: u32, registro: &T) {
+            ink::env::set_contract_storage::<(u32, u32), T>(&(K::KEY, id), registro);
+        }
+
+        /// Borra el registro que un índice por cuenta guardaba bajo la cuenta sola,
+        /// antes de pasar a indexar por (cuenta, posición).
+        fn borrar_registro_anterior<V: Packed, K: StorageKey>(_indice: &Mapping<(AccountId, u32), V, K>, cuenta: AccountId) {
+            ink::env::clear_contract_storage::<(u32, AccountId)>(&(K::KEY, cuenta));
+        }
+
+        /// Registra un usuario con un rol específico (Comprador, Vendedor o Ambos).
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - Retorna `UsuarioExistente` si la dirección ya está registrada.
+        #[ink(message)]
+        pub fn registrar_usuario(&mut self, rol: RolUsuario) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.registrar_usuario_interno(rol)
+        }
+
+        /// Consulta si un usuario está registrado en el sistema.
+        ///
+        /// # Retorna
+        /// - `true` si el usuario está registrado.
+        /// - `false` si el usuario no está registrado.
+        #[ink(message)]
+        pub fn esta_registrado(&self, usuario: AccountId) -> bool {
+            self.usuarios.contains(usuario)
+        }
+
+        /// Obtiene la información de un usuario registrado.
+        ///
+        /// # Retorna
+        /// - `Some(Usuario)` si el usuario está registrado.
+        /// - `None` si el usuario no está registrado.
+        #[ink(message)]
+        pub fn obtener_usuario(&self, usuario: AccountId) -> Option<Usuario> {
+            self.usuarios.get(usuario)
+        }
+
+        /// Obtiene el resumen público de reputación de un usuario.
+        ///
+        /// Los promedios se expresan en punto fijo multiplicados por `ESCALA_PROMEDIO`
+        /// (por ejemplo, `450` equivale a 4,5 estrellas) junto con la cantidad de calificaciones.
+        ///
+        /// # Retorna
+        /// - `Some(ResumenReputacion)` si el usuario está registrado.
+        /// - `None` si el usuario no está registrado.
+        #[ink(message)]
+        pub fn obtener_reputacion(&self, usuario: AccountId) -> Option<ResumenReputacion> {
+            self.usuarios.get(usuario).map(|u| ResumenReputacion {
+                promedio_como_comprador: u.reputacion_como_comprador.promedio(),
+                calificaciones_como_comprador: u.reputacion_como_comprador.cantidad_calificaciones,
+                promedio_como_vendedor: u.reputacion_como_vendedor.promedio(),
+                calificaciones_como_vendedor: u.reputacion_como_vendedor.cantidad_calificaciones,
+            })
+        }
+
+        /// Obtiene un producto publicado por su id.
+        ///
+        /// # Retorna
+        /// - `Some(Producto)` si el producto existe.
+        /// - `None` si el producto no existe.
+        #[ink(message)]
+        pub fn obtener_producto(&self, id: u32) -> Option<Producto> {
+            self.productos.get(id)
+        }
+
+        /// Obtiene una orden por su id.
+        ///
+        /// # Retorna
+        /// - `Some(Orden)` si la orden existe.
+        /// - `None` si la orden no existe.
+        #[ink(message)]
+        pub fn obtener_orden(&self, id: u32) -> Option<Orden> {
+            self.ordenes.get(id)
+        }
+
+        /// Lista los productos publicados por un vendedor, en orden de publicación,
+        /// a partir de la posición `desde`.
+        ///
+        /// Devuelve como máximo `limite` productos (acotado por `LIMITE_PAGINA`) y el
+        /// cursor a usar como `desde` en la próxima llamada, o `None` si no hay más.
+        #[ink(message)]
+        pub fn productos_de_vendedor(&self, cuenta: AccountId, desde: u32, limite: u32) -> Pagina<Producto> {
+            let total = self.cantidad_productos_por_vendedor.get(cuenta).unwrap_or(0);
+            let (posiciones, siguiente) = Self::rango_pagina(desde, limite, total);
+            Pagina {
+                elementos: posiciones
+                    .filter_map(|posicion| self.productos_por_vendedor.get((cuenta, posicion)))
+                    .filter_map(|id| self.productos.get(id))
+                    .collect(),
+                siguiente,
+            }
+        }
+
+        /// Cantidad de usuarios registrados en el marketplace.
+        #[ink(message)]
+        pub fn cantidad_usuarios(&self) -> u32 {
+            self.cantidad_usuarios
+        }
+
+        /// Lógica interna para registrar un usuario.
+        fn registrar_usuario_interno(&mut self, rol: RolUsuario) -> Result<(), SistemaError> {
+            let usuario_llamador = self.env().caller();
+            // Verifica si el usuario es existente
+            if self.usuarios.contains(usuario_llamador) { // Cambia contains_key por contains
+                return Err(SistemaError::UsuarioExistente);
+            }
+            if self.cantidad_usuarios == u32::MAX {
+                return Err(SistemaError::LimiteAlcanzado);
+            }
+            self.indexar_usuario(usuario_llamador);
+            // Si no existe, crea un nuevo usuario con los permisos del rol elegido
+            let nuevo_usuario = Usuario::new(usuario_llamador, Permisos::de_rol(&rol));
+            self.usuarios.insert(usuario_llamador, &nuevo_usuario);
+            self.env().emit_event(UsuarioRegistrado {
+                usuario: usuario_llamador,
+                rol,
+            });
+            Ok(())
+        }
+
+        /// Agrega una cuenta nueva al listado de cuentas registradas.
+        fn indexar_usuario(&mut self, cuenta: AccountId) {
+            self.cuentas_registradas.insert(self.cantidad_usuarios, &cuenta);
+            self.cantidad_usuarios = self.cantidad_usuarios.saturating_add(1);
+        }
+
+        /// Modifica el rol de un usuario registrado.
+        ///
+        /// El rol reemplaza los permisos de compra y venta del usuario; los
+        /// permisos de arbitraje y moderación se conservan.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el usuario no está registrado.
+        /// - `NoEsRolCorrecto` si el usuario ya está registrado con ese rol.
+        /// - `PermisoBloqueado` si el rol incluye un permiso que un moderador le revocó.
+        #[ink(message)]
+        pub fn modificar_rol_usuario(&mut self,nuevo_rol: RolUsuario,) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.modificar_rol_usuario_interno(nuevo_rol)
+        }
+
+        /// Lógica interna para modificar el rol de un usuario.
+        fn modificar_rol_usuario_interno(&mut self,nuevo_rol: RolUsuario,) -> Result<(), SistemaError> {
+            let usuario_llamador = self.env().caller();
+            // Verifica que el usuario esté registrado
+            let mut usuario = self.usuarios.get(usuario_llamador)
+                .ok_or(SistemaError::UsuarioNoRegistrado)?;
+
+            // Verifica que el usuario quiere cambiar a un rol que no es el actual
+            let rol_anterior = usuario.permisos.rol();
+            if rol_anterior.as_ref() == Some(&nuevo_rol) {
+                return Err(SistemaError::NoEsRolCorrecto);
+            }
+            // Un cambio de rol no puede devolver permisos revocados por un moderador
+            let bloqueados = self.permisos_bloqueados.get(usuario_llamador).unwrap_or_default();
+            if Permisos::de_rol(&nuevo_rol).comparte_alguno(bloqueados) {
+                return Err(SistemaError::PermisoBloqueado);
+            }
+
+            // Reemplaza los permisos de compra y venta por los del nuevo rol
+            usuario.permisos.revocar(Permiso::Comprar);
+            usuario.permisos.revocar(Permiso::Vender);
+            usuario.permisos.agregar(Permisos::de_rol(&nuevo_rol));
+            self.usuarios.insert(usuario_llamador, &usuario);
+            self.env().emit_event(RolModificado {
+                usuario: usuario_llamador,
+                rol_anterior,
+                rol_nuevo: nuevo_rol,
+            });
+            Ok(())
+        }
+
+        /// Permite a un usuario con rol de Vendedor publicar un producto.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el usuario no está registrado.
+        /// - `NoEsRolCorrecto` si el usuario no es vendedor.
+        /// - `CantidadInsuficiente` si la cantidad es 0.
+        #[ink(message)]
+        pub fn publicar_producto(
+            &mut self,
+            nombre: String,
+            descripcion: String,
+            precio: Balance,
+            cantidad: u32,
+            categoria: String,
+        ) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.crear_producto_seguro(nombre, descripcion, precio, cantidad, categoria, Moneda::Nativa)
+        }
+
+        /// Igual que `publicar_producto`, pero con el precio expresado en la moneda indicada.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el usuario no está registrado.
+        /// - `NoEsRolCorrecto` si el usuario no es vendedor.
+        /// - `CantidadInsuficiente` si la cantidad es 0.
+        /// - `TokenNoPermitido` si el token no está en la lista de permitidos.
+        #[ink(message)]
+        pub fn publicar_producto_con_moneda(
+            &mut self,
+            nombre: String,
+            descripcion: String,
+            precio: Balance,
+            cantidad: u32,
+            categoria: String,
+            moneda: Moneda,
+        ) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.crear_producto_seguro(nombre, descripcion, precio, cantidad, categoria, moneda)
+        }
+
+        /// Lógica interna para validar y agregar un producto.
+        fn crear_producto_seguro(
+            &mut self,
+            nombre: String,
+            descripcion: String,
+            precio: Balance,
+            cantidad: u32,
+            categoria: String,
+            moneda: Moneda,
+        ) -> Result<(), SistemaError> {
+            let vendedor = self.env().caller();
+            // Verifica que el vendedor esté registrado y tenga permiso de venta
+            self.verificar_permiso(vendedor, Permiso::Vender)?;
+            // Verifica que la cantidad y la moneda sean válidas
+            self.verificar_cantidad(cantidad)?;
+            self.verificar_moneda(moneda)?;
+            // Agrega el producto al marketplace
+            let mut producto = Producto::new(0, nombre, descripcion, precio, cantidad, categoria, vendedor);
+            producto.moneda = moneda;
+            self.agregar_producto(producto)
+        }
+
+        /// Permite al vendedor de un producto cambiar su descripción y su precio.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller no está registrado.
+        /// - `ProductosVacios` si el producto no existe.
+        /// - `NoEsRolCorrecto` si el caller no es el vendedor del producto.
+        /// - `ProductoNoDisponible` si el producto fue dado de baja.
+        #[ink(message)]
+        pub fn editar_producto(&mut self, producto_id: u32, descripcion: String, precio: Balance) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.editar_producto_interno(producto_id, descripcion, precio)
+        }
+
+        /// Permite al vendedor de un producto sumar unidades a su stock.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller no está registrado.
+        /// - `CantidadInsuficiente` si la cantidad es 0.
+        /// - `ProductosVacios` si el producto no existe.
+        /// - `NoEsRolCorrecto` si el caller no es el vendedor del producto.
+        /// - `ProductoNoDisponible` si el producto fue dado de baja.
+        #[ink(message)]
+        pub fn reponer_stock(&mut self, producto_id: u32, cantidad: u32) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.reponer_stock_interno(producto_id, cantidad)
+        }
+
+        /// Permite al vendedor de un producto quitar unidades de su stock, por ejemplo
+        /// si se dañaron o se vendieron por fuera del marketplace.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller no está registrado.
+        /// - `CantidadInsuficiente` si la cantidad es 0.
+        /// - `ProductosVacios` si el producto no existe.
+        /// - `NoEsRolCorrecto` si el caller no es el vendedor del producto.
+        /// - `ProductoNoDisponible` si el producto fue dado de baja.
+        /// - `StockInsuficiente` si la cantidad supera el stock actual.
+        #[ink(message)]
+        pub fn reducir_stock(&mut self, producto_id: u32, cantidad: u32) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.reducir_stock_interno(producto_id, cantidad)
+        }
+
+        /// Permite al vendedor pausar un producto activo: deja de aceptar órdenes
+        /// hasta que se reactive.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller no está registrado.
+        /// - `ProductosVacios` si el producto no existe.
+        /// - `NoEsRolCorrecto` si el caller no es el vendedor del producto.
+        /// - `ProductoNoDisponible` si el producto fue dado de baja.
+        /// - `EstadoInvalido` si el producto no está activo.
+        #[ink(message)]
+        pub fn pausar_producto(&mut self, producto_id: u32) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.actualizar_estado_producto(producto_id, EstadoProducto::Pausado)
+        }
+
+        /// Permite al vendedor volver a activar un producto pausado.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller no está registrado.
+        /// - `ProductosVacios` si el producto no existe.
+        /// - `NoEsRolCorrecto` si el caller no es el vendedor del producto.
+        /// - `ProductoNoDisponible` si el producto fue dado de baja.
+        /// - `EstadoInvalido` si el producto no está pausado.
+        #[ink(message)]
+        pub fn reactivar_producto(&mut self, producto_id: u32) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.actualizar_estado_producto(producto_id, EstadoProducto::Activo)
+        }
+
+        /// Permite al vendedor dar de baja un producto de forma definitiva.
+        /// Las órdenes ya creadas sobre el producto siguen su curso normal.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller no está registrado.
+        /// - `ProductosVacios` si el producto no existe.
+        /// - `NoEsRolCorrecto` si el caller no es el vendedor del producto.
+        /// - `ProductoNoDisponible` si el producto ya fue dado de baja.
+        #[ink(message)]
+        pub fn dar_de_baja_producto(&mut self, producto_id: u32) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.actualizar_estado_producto(producto_id, EstadoProducto::DadoDeBaja)
+        }
+
+        /// Lógica interna para editar la descripción y el precio de un producto.
+        fn editar_producto_interno(&mut self, producto_id: u32, descripcion: String, precio: Balance) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            let mut producto = self.obtener_producto_propio(caller, producto_id)?;
+            producto.descripcion = descripcion;
+            producto.precio = precio;
+            self.productos.insert(producto_id, &producto);
+            self.env().emit_event(ProductoEditado { producto_id, precio });
+            Ok(())
+        }
+
+        /// Lógica interna para reponer el stock de un producto.
+        fn reponer_stock_interno(&mut self, producto_id: u32, cantidad: u32) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_cantidad(cantidad)?;
+            let mut producto = self.obtener_producto_propio(caller, producto_id)?;
+            self.devolver_stock(&mut producto, cantidad);
+            Ok(())
+        }
+
+        /// Lógica interna para reducir el stock de un producto.
+        fn reducir_stock_interno(&mut self, producto_id: u32, cantidad: u32) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_cantidad(cantidad)?;
+            let mut producto = self.obtener_producto_propio(caller, producto_id)?;
+            if cantidad > producto.cantidad {
+                return Err(SistemaError::StockInsuficiente);
+            }
+            self.descontar_stock(&mut producto, cantidad);
+            Ok(())
+        }
+
+        /// Lógica interna para pausar, reactivar o dar de baja un producto.
+        fn actualizar_estado_producto(&mut self, producto_id: u32, nuevo_estado: EstadoProducto) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            let mut producto = self.obtener_producto_propio(caller, producto_id)?;
+            self.verificar_transicion_producto(&producto.estado, &nuevo_estado)?;
+            let estado_anterior = core::mem::replace(&mut producto.estado, nuevo_estado.clone());
+            self.productos.insert(producto_id, &producto);
+            self.env().emit_event(EstadoProductoActualizado {
+                producto_id,
+                estado_anterior,
+                estado_nuevo: nuevo_estado,
+            });
+            Ok(())
+        }
+
+        /// Permite a un usuario con rol de Comprador crear una orden de compra.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el usuario no está registrado.
+        /// - `NoEsRolCorrecto` si el usuario no es comprador.
+        /// - `ProductosVacios` si el producto no existe.
+        /// - `CantidadInsuficiente` si la cantidad solicitada es 0.
+        /// - `StockInsuficiente` si no hay suficiente stock disponible.
+        /// - `ProductoNoDisponible` si el producto está pausado o dado de baja.
+        /// - `TokenNoPermitido` si el precio está en un token que dejó de estar permitido.
+        /// - `AutoCompraNoPermitida` si el caller es el vendedor del producto.
+        /// - `PagoIncorrecto` si el valor transferido no es `precio * cantidad`, o no es cero
+        ///   en un producto con precio en token.
+        /// - `TransferenciaFallida` si no se pudo cobrar el monto en tokens.
+        /// 
+        /// # Nota
+        /// Esta función requiere que el usuario esté previamente registrado como Comprador o Ambos.
+        /// Si no está registrado, debe llamar primero a `registrar_usuario()`.
+        /// La función verifica el stock disponible antes de crear la orden y descuenta automáticamente
+        /// el stock del producto una vez confirmada la compra.
+        /// El pago queda retenido en el contrato hasta que la orden se recibe (se acredita en el
+        /// saldo del vendedor) o se cancela (se acredita en el saldo del comprador).
+        /// Si el precio está en un token PSP22, el comprador debe autorizar antes al
+        /// marketplace con `approve` por `precio * cantidad` y no adjuntar token nativo.
+        #[ink(message, payable)]
+        pub fn crear_orden(&mut self, producto_id: u32, cantidad: u32) -> Result<u32, SistemaError> {
+            self.verificar_operativo()?;
+            self.crear_nueva_orden(producto_id, cantidad)
+        }
+        
+        /// Lógica interna para crear una nueva orden de compra.
+        fn crear_nueva_orden(&mut self, producto_id: u32, cantidad: u32) -> Result<u32, SistemaError> {
+            let comprador = self.env().caller();
+            
+            // Validación temprana: verificar registro y permiso antes de cualquier otra operación
+            self.verificar_permiso(comprador, Permiso::Comprar)?;
+            
+            // Obtén el producto y el monto a pagar antes del mutable borrow
+            let (mut producto, monto) = self.validar_linea(comprador, producto_id, cantidad)?;
+            let vendedor = producto.vendedor;
+            let moneda = producto.moneda;
+            // En productos con precio en token no se acepta token nativo
+            let esperado = match moneda {
+                Moneda::Nativa => monto,
+                Moneda::Psp22(_) => 0,
+            };
+            self.verificar_pago(esperado)?;
+            // Los pagos en token se retiran de la cuenta del comprador, que debe haber
+            // autorizado al marketplace con `approve`
+            if let Moneda::Psp22(token) = moneda {
+                self.cobrar_token(token, comprador, monto)?;
+            }
+            
+            // Ahora sí descontar el stock del producto
+            self.descontar_stock(&mut producto, cantidad);
+            
+            let mut orden = Orden::new(0, comprador, vendedor, producto_id, cantidad, monto);
+            orden.moneda = moneda;
+            self.crear_y_emitir_orden(orden)
+        }
+
+        /// Permite a un comprador comprar varios productos en una sola transacción.
+        ///
+        /// Recibe pares `(producto_id, cantidad)`. Las líneas se agrupan en una orden por
+        /// vendedor, o por vendedor y moneda si un vendedor publica en más de una, y las
+        /// líneas repetidas de un mismo producto se suman. Todas las líneas se validan
+        /// antes de descontar stock, así que se crean todas las órdenes o ninguna.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el usuario no está registrado.
+        /// - `NoEsRolCorrecto` si el usuario no es comprador.
+        /// - `CarritoInvalido` si el carrito está vacío o tiene más de `LIMITE_CARRITO` líneas.
+        /// - `CantidadExcedida` si las líneas repetidas de un producto suman más de `u32::MAX`.
+        /// - Los errores de `crear_orden` que correspondan a cualquiera de las líneas.
+        /// - `PagoIncorrecto` si el valor transferido no es la suma de las líneas con precio
+        ///   en token nativo.
+        /// - `LimiteAlcanzado` si no quedan ids para todas las órdenes.
+        ///
+        /// # Nota
+        /// Para las líneas con precio en un token PSP22, el comprador debe autorizar antes
+        /// al marketplace con `approve` por el total de ese token en el carrito.
+        #[ink(message, payable)]
+        pub fn crear_orden_multiple(&mut self, lineas: Vec<(u32, u32)>) -> Result<Vec<u32>, SistemaError> {
+            self.verificar_operativo()?;
+            self.crear_ordenes_de_carrito(lineas)
+        }
+
+        /// Lógica interna para crear las órdenes de un carrito.
+        fn crear_ordenes_de_carrito(&mut self, lineas: Vec<(u32, u32)>) -> Result<Vec<u32>, SistemaError> {
+            let comprador = self.env().caller();
+            self.verificar_permiso(comprador, Permiso::Comprar)?;
+            if lineas.is_empty() || lineas.len() > LIMITE_CARRITO as usize {
+                return Err(SistemaError::CarritoInvalido);
+            }
+
+            // Suma las líneas repetidas de un mismo producto, respetando el orden del carrito
+            let mut cantidades: Vec<(u32, u32)> = Vec::with_capacity(lineas.len());
+            for (producto_id, cantidad) in lineas {
+                self.verificar_cantidad(cantidad)?;
+                match cantidades.iter_mut().find(|(id, _)| *id == producto_id) {
+                    Some((_, total)) => {
+                        *total = total.checked_add(cantidad).ok_or(SistemaError::CantidadExcedida)?;
+                    }
+                    None => cantidades.push((producto_id, cantidad)),
+                }
+            }
+
+            // Valida todas las líneas y las agrupa antes de modificar nada
+            let mut productos = Vec::with_capacity(cantidades.len());
+            let mut grupos: Vec<(AccountId, Moneda, Vec<LineaOrden>)> = Vec::new();
+            let mut total_nativo: Balance = 0;
+            for (producto_id, cantidad) in cantidades {
+                let (producto, monto) = self.validar_linea(comprador, producto_id, cantidad)?;
+                if producto.moneda == Moneda::Nativa {
+                    total_nativo = total_nativo.checked_add(monto).ok_or(SistemaError::PagoIncorrecto)?;
+                }
+                let linea = LineaOrden { producto_id, cantidad, monto };
+                match grupos
+                    .iter_mut()
+                    .find(|(vendedor, moneda, _)| *vendedor == producto.vendedor && *moneda == producto.moneda)
+                {
+                    Some((_, _, lineas)) => lineas.push(linea),
+                    None => grupos.push((producto.vendedor, producto.moneda, ink::prelude::vec![linea])),
+                }
+                productos.push((producto, cantidad));
+            }
+            self.verificar_pago(total_nativo)?;
+            self.siguiente_id_orden
+                .checked_add(grupos.len() as u32)
+                .ok_or(SistemaError::LimiteAlcanzado)?;
+
+            // Cada orden en token se cobra por separado
+            let mut ordenes = Vec::with_capacity(grupos.len());
+            for (vendedor, moneda, lineas) in grupos {
+                let mut orden = Orden::con_lineas(0, comprador, vendedor, lineas);
+                orden.moneda = moneda;
+                if let Moneda::Psp22(token) = moneda {
+                    self.cobrar_token(token, comprador, orden.monto)?;
+                }
+                ordenes.push(orden);
+            }
+
+            for (mut producto, cantidad) in productos {
+                self.descontar_stock(&mut producto, cantidad);
+            }
+            ordenes.into_iter().map(|orden| self.crear_y_emitir_orden(orden)).collect()
+        }
+
+        /// Permite al vendedor marcar una orden como enviada.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `NoEsRolCorrecto` si el caller no es el vendedor de la orden.
+        /// - `EstadoInvalido` si la transición de estado no es válida.
+        #[ink(message)]
+        pub fn marcar_orden_como_enviada(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.actualizar_estado_orden(orden_id, EstadoOrden::Enviada)
+        }
+
+        /// Permite al comprador marcar una orden como recibida.
+        /// El pago retenido se acredita en el saldo del vendedor, que lo retira con `retirar_fondos`.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `NoEsRolCorrecto` si el caller no es el comprador de la orden.
+        /// - `EstadoInvalido` si la transición de estado no es válida.
+        #[ink(message)]
+        pub fn marcar_como_recibida(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.actualizar_estado_orden(orden_id, EstadoOrden::Recibida)
+        }
+
+        /// Permite a una de las partes de la orden solicitar su cancelación.
+        ///
+        /// La cancelación requiere consentimiento mutuo: la contraparte debe
+        /// confirmarla luego con `aceptar_cancelacion`.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller no está registrado.
+        /// - `OrdenNoExiste` si la orden no existe.
+        /// - `NoEsRolCorrecto` si el caller no es comprador ni vendedor de la orden.
+        /// - `EstadoInvalido` si la orden no está pendiente.
+        /// - `CancelacionYaSolicitada` si ya hay una solicitud de cancelación en curso.
+        #[ink(message)]
+        pub fn solicitar_cancelacion(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.solicitar_cancelacion_interno(orden_id)
+        }
+
+        /// Permite a la contraparte aceptar una cancelación solicitada.
+        /// La orden pasa a `Cancelada`, el stock reservado vuelve al producto
+        /// y el pago retenido se acredita en el saldo del comprador, que lo
+        /// retira con `retirar_fondos`.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller no está registrado.
+        /// - `OrdenNoExiste` si la orden no existe.
+        /// - `NoEsRolCorrecto` si el caller no es comprador ni vendedor de la orden.
+        /// - `EstadoInvalido` si la orden no está pendiente.
+        /// - `CancelacionNoSolicitada` si la contraparte no solicitó la cancelación.
+        #[ink(message)]
+        pub fn aceptar_cancelacion(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.aceptar_cancelacion_interno(orden_id)
+        }
+
+        /// Lógica interna para registrar una solicitud de cancelación.
+        fn solicitar_cancelacion_interno(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_registro(caller)?;
+            let mut orden = self.buscar_orden(orden_id)?;
+            self.verificar_parte_de_orden(caller, &orden)?;
+            self.verificar_transicion_estado(&orden.estado, &EstadoOrden::Cancelada)?;
+            // Solo puede haber una solicitud en curso, la otra parte debe aceptarla
+            if orden.comprador_acepta_cancelacion || orden.vendedor_acepta_cancelacion {
+                return Err(SistemaError::CancelacionYaSolicitada);
+            }
+            if caller == orden.comprador {
+                orden.comprador_acepta_cancelacion = true;
+            } else {
+                orden.vendedor_acepta_cancelacion = true;
+            }
+            self.ordenes.insert(orden_id, &orden);
+            self.env().emit_event(CancelacionSolicitada {
+                orden_id,
+                solicitante: caller,
+            });
+            Ok(())
+        }
+
+        /// Lógica interna para aceptar una cancelación y devolver el stock.
+        fn aceptar_cancelacion_interno(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_registro(caller)?;
+            let mut orden = self.buscar_orden(orden_id)?;
+            self.verificar_parte_de_orden(caller, &orden)?;
+            self.verificar_transicion_estado(&orden.estado, &EstadoOrden::Cancelada)?;
+            // La contraparte del caller tiene que haber solicitado la cancelación
+            let contraparte_solicito = if caller == orden.comprador {
+                orden.vendedor_acepta_cancelacion
+            } else {
+                orden.comprador_acepta_cancelacion
+            };
+            if !contraparte_solicito {
+                return Err(SistemaError::CancelacionNoSolicitada);
+            }
+            orden.comprador_acepta_cancelacion = true;
+            orden.vendedor_acepta_cancelacion = true;
+            // Reembolsa al comprador el pago retenido
+            let (comprador, moneda, monto) = (orden.comprador, orden.moneda, orden.monto);
+            self.cancelar_orden(orden)?;
+            self.acreditar_saldo(comprador, moneda, monto);
+            Ok(())
+        }
+
+        /// Permite a cualquier cuenta cerrar una orden abandonada una vez vencido su plazo.
+        ///
+        /// - Una orden `Pendiente` que el vendedor no envió dentro de `PLAZO_ENVIO` desde
+        ///   su creación pasa a `Cancelada`: el stock vuelve a los productos y el pago se
+        ///   acredita en el saldo del comprador, que lo retira con `retirar_fondos`.
+        ///   Así una transferencia rechazada por el comprador no impide cerrar la orden.
+        /// - Una orden `Enviada` cuya recepción el comprador no confirmó dentro de
+        ///   `PLAZO_CONFIRMACION` desde el envío pasa a `Recibida` y el pago se acredita
+        ///   al vendedor.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `OrdenNoExiste` si la orden no existe.
+        /// - `EstadoInvalido` si la orden no está pendiente ni enviada.
+        /// - `PlazoNoVencido` si todavía no venció el plazo de la orden.
+        #[ink(message)]
+        pub fn finalizar_por_timeout(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.finalizar_por_timeout_interno(orden_id)
+        }
+
+        /// Lógica interna para cerrar una orden con el plazo vencido.
+        fn finalizar_por_timeout_interno(&mut self, orden_id: u32) -> Result<(), SistemaError> {
+            let mut orden = self.buscar_orden(orden_id)?;
+            let ahora = self.env().block_timestamp();
+            match orden.estado {
+                EstadoOrden::Pendiente => {
+                    Self::verificar_plazo_vencido(orden.creada_en, PLAZO_ENVIO, ahora)?;
+                    let (comprador, moneda, monto) = (orden.comprador, orden.moneda, orden.monto);
+                    self.cancelar_orden(orden)?;
+                    self.acreditar_saldo(comprador, moneda, monto);
+                    Ok(())
+                }
+                EstadoOrden::Enviada => {
+                    let enviada_en = orden.enviada_en.unwrap_or(orden.creada_en);
+                    Self::verificar_plazo_vencido(enviada_en, PLAZO_CONFIRMACION, ahora)?;
+                    let monto = orden.monto;
+                    self.liquidar_al_vendedor(&mut orden, monto);
+                    self.cambiar_estado_orden(orden, EstadoOrden::Recibida);
+                    Ok(())
+                }
+                _ => Err(SistemaError::EstadoInvalido),
+            }
+        }
+
+        /// Cancela una orden pendiente ya validada y devuelve el stock reservado a
+        /// cada producto. El reembolso del pago retenido queda a cargo del llamador.
+        fn cancelar_orden(&mut self, orden: Orden) -> Result<(), SistemaError> {
+            let mut productos = Vec::with_capacity(orden.lineas.len());
+            for linea in &orden.lineas {
+                productos.push((self.buscar_producto(linea.producto_id)?, linea.cantidad));
+            }
+
+            self.cambiar_estado_orden(orden, EstadoOrden::Cancelada);
+
+            // Devuelve el stock reservado a cada producto
+            for (mut producto, cantidad) in productos {
+                self.devolver_stock(&mut producto, cantidad);
+            }
+            Ok(())
+        }
+
+        /// Permite al comprador calificar al vendedor de una orden recibida.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller no está registrado.
+        /// - `PuntajeInvalido` si el puntaje no está entre 1 y 5.
+        /// - `OrdenNoExiste` si la orden no existe.
+        /// - `NoEsRolCorrecto` si el caller no es el comprador de la orden.
+        /// - `OrdenNoRecibida` si la orden todavía no fue recibida.
+        /// - `AutoCompraNoPermitida` si comprador y vendedor son la misma cuenta.
+        /// - `YaCalificado` si el comprador ya calificó esta orden.
+        #[ink(message)]
+        pub fn calificar_vendedor(&mut self, orden_id: u32, puntaje: u8) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.calificar_vendedor_interno(orden_id, puntaje)
+        }
+
+        /// Permite al vendedor calificar al comprador de una orden recibida.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller no está registrado.
+        /// - `PuntajeInvalido` si el puntaje no está entre 1 y 5.
+        /// - `OrdenNoExiste` si la orden no existe.
+        /// - `NoEsRolCorrecto` si el caller no es el vendedor de la orden.
+        /// - `OrdenNoRecibida` si la orden todavía no fue recibida.
+        /// - `AutoCompraNoPermitida` si comprador y vendedor son la misma cuenta.
+        /// - `YaCalificado` si el vendedor ya calificó esta orden.
+        #[ink(message)]
+        pub fn calificar_comprador(&mut self, orden_id: u32, puntaje: u8) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.calificar_comprador_interno(orden_id, puntaje)
+        }
+
+        /// Lógica interna para que el comprador califique al vendedor.
+        fn calificar_vendedor_interno(&mut self, orden_id: u32, puntaje: u8) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_registro(caller)?;
+            self.verificar_puntaje(puntaje)?;
+            let mut orden = self.buscar_orden(orden_id)?;
+            if caller != orden.comprador {
+                return Err(SistemaError::NoEsRolCorrecto);
+            }
+            self.verificar_no_es_autocompra(orden.comprador, orden.vendedor)?;
+            self.verificar_orden_calificable(&orden, orden.comprador_califico)?;
+            let vendedor = orden.vendedor;
+            // Acumula el puntaje en la reputación del vendedor
+            let mut usuario = self.usuarios.get(vendedor)
+                .ok_or(SistemaError::UsuarioNoRegistrado)?;
+            usuario.reputacion_como_vendedor.registrar(puntaje);
+            self.usuarios.insert(vendedor, &usuario);
+
+            orden.comprador_califico = true;
+            orden.puntaje_al_vendedor = Some(puntaje);
+            self.ordenes.insert(orden_id, &orden);
+            self.env().emit_event(CalificacionRegistrada {
+                orden_id,
+                calificador: caller,
+                calificado: vendedor,
+                puntaje,
+            });
+            Ok(())
+        }
+
+        /// Lógica interna para que el vendedor califique al comprador.
+        fn calificar_comprador_interno(&mut self, orden_id: u32, puntaje: u8) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_registro(caller)?;
+            self.verificar_puntaje(puntaje)?;
+            let mut orden = self.buscar_orden(orden_id)?;
+            if caller != orden.vendedor {
+                return Err(SistemaError::NoEsRolCorrecto);
+            }
+            self.verificar_no_es_autocompra(orden.comprador, orden.vendedor)?;
+            self.verificar_orden_calificable(&orden, orden.vendedor_califico)?;
+            let comprador = orden.comprador;
+            // Acumula el puntaje en la reputación del comprador
+            let mut usuario = self.usuarios.get(comprador)
+                .ok_or(SistemaError::UsuarioNoRegistrado)?;
+            usuario.reputacion_como_comprador.registrar(puntaje);
+            self.usuarios.insert(comprador, &usuario);
+
+            orden.vendedor_califico = true;
+            self.ordenes.insert(orden_id, &orden);
+            self.env().emit_event(CalificacionRegistrada {
+                orden_id,
+                calificador: caller,
+                calificado: comprador,
+                puntaje,
+            });
+            Ok(())
+        }
+
+        /// Permite a una de las partes abrir una disputa sobre una orden enviada.
+        ///
+        /// El motivo se guarda como hash del documento con la descripción del reclamo.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `UsuarioNoRegistrado` si el caller no está registrado.
+        /// - `OrdenNoExiste` si la orden no existe.
+        /// - `NoEsRolCorrecto` si el caller no es comprador ni vendedor de la orden.
+        /// - `AutoCompraNoPermitida` si comprador y vendedor son la misma cuenta.
+        /// - `EstadoInvalido` si la orden no está enviada.
+        #[ink(message)]
+        pub fn abrir_disputa(&mut self, orden_id: u32, motivo_hash: Hash) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.abrir_disputa_interno(orden_id, motivo_hash)
+        }
+
+        /// Permite a un árbitro resolver una disputa abierta.
+        ///
+        /// - `ReembolsarComprador`: acredita el pago en el saldo del comprador y la orden pasa a `Cancelada`.
+        /// - `PagarVendedor`: acredita el pago en el saldo del vendedor y la orden pasa a `Recibida`.
+        /// - `Dividir`: reparte el pago entre ambos saldos y la orden pasa a `Recibida`.
+        ///
+        /// Cada parte retira lo acreditado con `retirar_fondos`. El stock no se repone
+        /// porque la mercadería ya fue enviada.
+        ///
+        /// # Errores
+        /// - `ContratoPausado` si el contrato está pausado.
+        /// - `MigracionPendiente` si falta migrar el almacenamiento.
+        /// - `NoEsArbitro` si el caller no es árbitro.
+        /// - `OrdenNoExiste` si la orden no existe.
+        /// - `NoEsRolCorrecto` si el árbitro es parte de la orden.
+        /// - `EstadoInvalido` si la orden no está en disputa.
+        /// - `MontoInvalido` si el monto para el comprador supera el pago retenido.
+        #[ink(message)]
+        pub fn resolver_disputa(&mut self, orden_id: u32, resolucion: ResolucionDisputa) -> Result<(), SistemaError> {
+            self.verificar_operativo()?;
+            self.resolver_disputa_interno(orden_id, resolucion)
+        }
+
+        /// Lógica interna para abrir una disputa.
+        fn abrir_disputa_interno(&mut self, orden_id: u32, motivo_hash: Hash) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_registro(caller)?;
+            let mut orden = self.buscar_orden(orden_id)?;
+            self.verificar_parte_de_orden(caller, &orden)?;
+            self.verificar_no_es_autocompra(orden.comprador, orden.vendedor)?;
+            self.verificar_transicion_estado(&orden.estado, &EstadoOrden::EnDisputa)?;
+            orden.disputa = Some(Disputa {
+                iniciador: caller,
+                motivo_hash,
+                resolucion: None,
+            });
+            self.cambiar_estado_orden(orden, EstadoOrden::EnDisputa);
+            Ok(())
+        }
+
+        /// Lógica interna para resolver una disputa y repartir el pago retenido.
+        fn resolver_disputa_interno(&mut self, orden_id: u32, resolucion: ResolucionDisputa) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_permiso(caller, Permiso::Arbitrar)
+                .map_err(|_| SistemaError::NoEsArbitro)?;
+            let mut orden = self.buscar_orden(orden_id)?;
+            // Un árbitro no puede resolver una disputa en la que participa
+            if self.verificar_parte_de_orden(caller, &orden).is_ok() {
+                return Err(SistemaError::NoEsRolCorrecto);
+            }
+            if orden.estado != EstadoOrden::EnDisputa {
+                return Err(SistemaError::EstadoInvalido);
+            }
+            let monto = orden.monto;
+
+            // Calcula cuánto recibe cada parte y el estado final de la orden
+            let (monto_comprador, estado_final) = match resolucion {
+                ResolucionDisputa::ReembolsarComprador => (monto, EstadoOrden::Cancelada),
+                ResolucionDisputa::PagarVendedor => (0, EstadoOrden::Recibida),
+                ResolucionDisputa::Dividir { monto_comprador } => {
+                    if monto_comprador > monto {
+                        return Err(SistemaError::MontoInvalido);
+                    }
+                    (monto_comprador, EstadoOrden::Recibida)
+                }
+            };
+            self.acreditar_saldo(orden.comprador, orden.moneda, monto_comprador);
+            self.liquidar_al_vendedor(&mut orden, monto.saturating_sub(monto_comprador));
+
+            if let Some(disputa) = orden.disputa.as_mut() {
+                disputa.resolucion = Some(resolucion);
+            }
+            self.cambiar_estado_orden(orden, estado_final);
+            Ok(())
+        }
+
+        /// Lógica interna para actualizar el estado de una orden.
+        fn actualizar_estado_orden(&mut self, orden_id: u32, nuevo_estado: EstadoOrden) -> Result<(), SistemaError> {
+            let caller = self.env().caller();
+            self.verificar_registro(caller)?;
+            // Primero obten la orden para verificar el permiso
+            let mut orden = self.buscar_orden(orden_id)?;
+            self.verificar_permiso_orden(caller, &orden, &nuevo_estado)?;
+            // El plazo de confirmación del comprador corre desde el envío
+            if nuevo_estado == EstadoOrden::Enviada {
+                orden.enviada_en = Some(self.env().block_timestamp());
+            }
+            // Al recibirse la orden se acredita el pago retenido al vendedor
+            if nuevo_estado == EstadoOrden::Recibida {
+                let monto = orden.monto;
+                self.liquidar_al_vendedor(&mut orden, monto);
+            }
+            // Luego modifica el estado
+            self.cambiar_estado_orden(orden, nuevo_estado);
+            Ok(())
+        }
+
+        /// Cambia el estado de una orden ya validada, la guarda y emite el evento de la transición.
+        /// Si la orden pasa a `Recibida`, cuenta sus unidades como vendidas.
+        fn cambiar_estado_orden(&mut self, mut orden: Orden, nuevo_estado: EstadoOrden) {
+            if nuevo_estado == EstadoOrden::Recibida {
+                for linea in &orden.lineas {
+                    self.registrar_venta(linea.producto_id, linea.cantidad);
+                }
+            }
+            let estado_anterior = core::mem::replace(&mut orden.estado, nuevo_estado.clone());
+            self.ordenes.insert(orden.id, &orden);
+            self.env().emit_event(EstadoOrdenActualizado {
+                orden_id: orden.id,
+                estado_anterior,
+                estado_nuevo: nuevo_estado,
+            });
+        }
+
+
+        // --- Funciones auxiliares ---
+
+        /// Verifica si un usuario está registrado.
+        fn verificar_registro(&self, usuario: AccountId) -> Result<(), SistemaError> {
+            if !self.usuarios.contains(usuario) { // Cambia contains_key por contains
+                Err(SistemaError::UsuarioNoRegistrado)
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Verifica que el caller sea el propietario del contrato.
+        fn verificar_propietario(&self, caller: AccountId) -> Result<(), SistemaError> {
+            if caller != self.propietario {
+                return Err(SistemaError::NoEsPropietario);
+            }
+            Ok(())
+        }
+
+        /// Verifica que el caller sea el propietario o un administrador del contrato.
+        fn verificar_administrador(&self, caller: AccountId) -> Result<(), SistemaError> {
+            if caller != self.propietario && !self.es_administrador(caller) {
+                return Err(SistemaError::NoEsAdministrador);
+            }
+            Ok(())
+        }
+
+        /// Verifica que el contrato no esté pausado.
+        fn verificar_no_pausado(&self) -> Result<(), SistemaError> {
+            if self.pausado {
+                return Err(SistemaError::ContratoPausado);
+            }
+            Ok(())
+        }
+
+        /// Verifica que el contrato pueda modificar su estado: no está pausado y
+        /// los registros están en el formato de la versión actual.
+        fn verificar_operativo(&self) -> Result<(), SistemaError> {
+            self.verificar_no_pausado()?;
+            if self.version_almacenamiento != VERSION_ALMACENAMIENTO {
+                return Err(SistemaError::MigracionPendiente);
+            }
+            Ok(())
+        }
+
+        /// Verifica que el usuario esté registrado y tenga el permiso requerido.
+        /// Es el único chequeo de autorización que usan los mensajes.
+        fn verificar_permiso(&self, usuario: AccountId, permiso: Permiso) -> Result<(), SistemaError> {
+            let usuario_data = self.usuarios.get(usuario)
+                .ok_or(SistemaError::UsuarioNoRegistrado)?;
+            if usuario_data.permisos.contiene(permiso) {
+                Ok(())
+            } else {
+                Err(SistemaError::NoEsRolCorrecto)
+            }
+        }
+
+        /// Verifica que la cantidad sea mayor a cero.
+        fn verificar_cantidad(&self, cantidad: u32) -> Result<(), SistemaError> {
+            if cantidad == 0 {
+                Err(SistemaError::CantidadInsuficiente)
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Verifica que ya pasó el plazo contado desde `desde`.
+        fn verificar_plazo_vencido(desde: Timestamp, plazo: Timestamp, ahora: Timestamp) -> Result<(), SistemaError> {
+            if ahora < desde.saturating_add(plazo) {
+                Err(SistemaError::PlazoNoVencido)
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Verifica que hay suficiente stock disponible para la cantidad solicitada.
+        fn verificar_stock_disponible(&self, producto: &Producto, cantidad_solicitada: u32) -> Result<(), SistemaError> {
+            if producto.cantidad < cantidad_solicitada {
+                Err(SistemaError::StockInsuficiente)
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Verifica que el valor transferido sea exactamente el monto esperado en token nativo.
+        fn verificar_pago(&self, esperado: Balance) -> Result<(), SistemaError> {
+            if self.env().transferred_value() != esperado {
+                Err(SistemaError::PagoIncorrecto)
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Verifica que el comprador pueda llevarse `cantidad` unidades de un producto y
+        /// retorna el producto junto con el monto a pagar por ellas.
+        fn validar_linea(&self, comprador: AccountId, producto_id: u32, cantidad: u32) -> Result<(Producto, Balance), SistemaError> {
+            // Validar que la cantidad solicitada sea válida
+            self.verificar_cantidad(cantidad)?;
+            let producto = self.buscar_producto(producto_id)?;
+            // Solo se puede comprar un producto activo
+            if producto.estado != EstadoProducto::Activo {
+                return Err(SistemaError::ProductoNoDisponible);
+            }
+            // El token del precio tiene que seguir permitido
+            self.verificar_moneda(producto.moneda)?;
+            self.verificar_no_es_autocompra(comprador, producto.vendedor)?;
+            // Verificar stock disponible antes de proceder
+            self.verificar_stock_disponible(&producto, cantidad)?;
+            let monto = producto.precio
+                .checked_mul(cantidad as Balance)
+                .ok_or(SistemaError::PagoIncorrecto)?;
+            Ok((producto, monto))
+        }
+
+        /// Verifica que una moneda pueda usarse para publicar productos.
+        fn verificar_moneda(&self, moneda: Moneda) -> Result<(), SistemaError> {
+            match moneda {
+                Moneda::Psp22(token) if !self.es_token_permitido(token) => Err(SistemaError::TokenNoPermitido),
+                _ => Ok(()),
+            }
+        }
+
+        /// Retira de la cuenta del comprador el monto de una orden en tokens.
+        fn cobrar_token(&mut self, token: AccountId, comprador: AccountId, monto: Balance) -> Result<(), SistemaError> {
+            let destino = self.env().account_id();
+            let mut psp22: ink::contract_ref!(PSP22) = token.into();
+            match psp22.call_mut().transfer_from(comprador, destino, monto, Vec::new()).try_invoke() {
+                Ok(Ok(Ok(()))) => Ok(()),
+                _ => Err(SistemaError::TransferenciaFallida),
+            }
+        }
+
+        /// Transfiere fondos retenidos por el contrato a la cuenta indicada, en la moneda indicada.
+        fn transferir_fondos(&mut self, moneda: Moneda, destino: AccountId, monto: Balance) -> Result<(), SistemaError> {
+            if monto == 0 {
+                return Ok(());
+            }
+            match moneda {
+                Moneda::Nativa => self.env()
+                    .transfer(destino, monto)
+                    .map_err(|_| SistemaError::TransferenciaFallida),
+                Moneda::Psp22(token) => {
+                    let mut psp22: ink::contract_ref!(PSP22) = token.into();
+                    match psp22.call_mut().transfer(destino, monto, Vec::new()).try_invoke() {
+                        Ok(Ok(Ok(()))) => Ok(()),
+                        _ => Err(SistemaError::TransferenciaFallida),
+                    }
+                }
+            }
+        }
+
+        /// Acredita al vendedor de una orden un monto retenido descontando la comisión
+        /// vigente, que queda acumulada en la tesorería y registrada en la orden. El
+        /// vendedor lo retira luego con `retirar_fondos`, así una transferencia fallida
+        /// no bloquea la orden.
+        fn liquidar_al_vendedor(&mut self, orden: &mut Orden, monto: Balance) {
+            let comision = Self::calcular_comision(monto, self.comision_bps());
+            if comision > 0 {
+                self.fijar_tesoreria(orden.moneda, self.tesoreria_en_moneda(orden.moneda).saturating_add(comision));
+            }
+            orden.comision = comision;
+            self.acreditar_saldo(orden.vendedor, orden.moneda, monto - comision);
+        }
+
+        /// Suma un monto al saldo retirable de una cuenta en una moneda.
+        fn acreditar_saldo(&mut self, cuenta: AccountId, moneda: Moneda, monto: Balance) {
+            let saldo = self.saldo_en_moneda(cuenta, moneda).saturating_add(monto);
+            self.fijar_saldo(cuenta, moneda, saldo);
+        }
+
+        /// Guarda el saldo retirable de una cuenta en una moneda.
+        fn fijar_saldo(&mut self, cuenta: AccountId, moneda: Moneda, saldo: Balance) {
+            match moneda {
+                Moneda::Nativa => self.saldos.insert(cuenta, &saldo),
+                Moneda::Psp22(token) => self.saldos_token.insert((cuenta, token), &saldo),
+            };
+        }
+
+        /// Guarda las comisiones acumuladas en una moneda.
+        fn fijar_tesoreria(&mut self, moneda: Moneda, monto: Balance) {
+            match moneda {
+                Moneda::Nativa => self.tesoreria.set(&monto),
+                Moneda::Psp22(token) => {
+                    self.tesoreria_token.insert(token, &monto);
+                }
+            }
+        }
+
+        /// Calcula la comisión sobre un monto sin desbordar para montos grandes.
+        fn calcular_comision(monto: Balance, comision_bps: u16) -> Balance {
+            let bps = Balance::from(comision_bps);
+            let total = Balance::from(BPS_TOTAL);
+            (monto / total) * bps + (monto % total) * bps / total
+        }
+
+        /// Agrega un producto al catálogo con el próximo id disponible.
+        fn agregar_producto(&mut self, mut nuevo_producto: Producto) -> Result<(), SistemaError> {
+            let id = self.siguiente_id_producto;
+            self.siguiente_id_producto = id.checked_add(1).ok_or(SistemaError::LimiteAlcanzado)?;
+            nuevo_producto.id = id;
+            let vendedor = nuevo_producto.vendedor;
+            self.env().emit_event(ProductoPublicado {
+                producto_id: id,
+                vendedor,
+                precio: nuevo_producto.precio,
+                moneda: nuevo_producto.moneda,
+                cantidad: nuevo_producto.cantidad,
+                categoria: nuevo_producto.categoria.clone(),
+            });
+            self.productos.insert(id, &nuevo_producto);
+            self.indexar_producto(vendedor, id);
+            Ok(())
+        }
+
+        /// Descuenta stock de un producto, lo guarda y emite el evento con el stock resultante.
+        fn descontar_stock(&mut self, producto: &mut Producto, cantidad: u32) {
+            producto.cantidad = producto.cantidad.saturating_sub(cantidad);
+            self.productos.insert(producto.id, producto);
+            self.env().emit_event(StockActualizado {
+                producto_id: producto.id,
+                stock: producto.cantidad,
+            });
+        }
+
+        /// Devuelve stock a un producto, lo guarda y emite el evento con el stock resultante.
+        fn devolver_stock(&mut self, producto: &mut Producto, cantidad: u32) {
+            producto.cantidad = producto.cantidad.saturating_add(cantidad);
+            self.productos.insert(producto.id, producto);
+            self.env().emit_event(StockActualizado {
+                producto_id: producto.id,
+                stock: producto.cantidad,
+            });
+        }
+
+        /// Suma unidades a las ventas concretadas de un producto.
+        fn registrar_venta(&mut self, producto_id: u32, cantidad: u32) {
+            if let Some(mut producto) = self.productos.get(producto_id) {
+                producto.unidades_vendidas = producto.unidades_vendidas.saturating_add(cantidad);
+                self.productos.insert(producto_id, &producto);
+            }
+        }
+
+        /// Obtiene un producto del caller que todavía no fue dado de baja.
+        fn obtener_producto_propio(&self, caller: AccountId, producto_id: u32) -> Result<Producto, SistemaError> {
+            self.verificar_registro(caller)?;
+            let producto = self.buscar_producto(producto_id)?;
+            if producto.vendedor != caller {
+                return Err(SistemaError::NoEsRolCorrecto);
+            }
+            if producto.estado == EstadoProducto::DadoDeBaja {
+                return Err(SistemaError::ProductoNoDisponible);
+            }
+            Ok(producto)
+        }
+
+        /// Verifica que la transición de estado del producto sea válida.
+        fn verificar_transicion_producto(
+            &self,
+            actual: &EstadoProducto,
+            nuevo: &EstadoProducto
+        ) -> Result<(), SistemaError> {
+            match (actual, nuevo) {
+                (EstadoProducto::Activo, EstadoProducto::Pausado) => Ok(()),
+                (EstadoProducto::Pausado, EstadoProducto::Activo) => Ok(()),
+                (EstadoProducto::Activo | EstadoProducto::Pausado, EstadoProducto::DadoDeBaja) => Ok(()),
+                _ => Err(SistemaError::EstadoInvalido),
+            }
+        }
+
+        /// Obtiene una copia de un producto por su id.
+        fn buscar_producto(&self, id: u32) -> Result<Producto, SistemaError> {
+            self.productos.get(id).ok_or(SistemaError::ProductosVacios)
+        }
+
+        /// Asigna el próximo id y el momento de creación a una orden nueva, la almacena y emite el evento.
+        fn crear_y_emitir_orden(&mut self, mut nueva_orden: Orden) -> Result<u32, SistemaError> {
+            let id = self.siguiente_id_orden;
+            self.siguiente_id_orden = id.checked_add(1).ok_or(SistemaError::LimiteAlcanzado)?;
+            nueva_orden.id = id;
+            nueva_orden.creada_en = self.env().block_timestamp();
+            self.ordenes.insert(id, &nueva_orden);
+            self.indexar_orden(nueva_orden.comprador, nueva_orden.vendedor, id);
+            self.env().emit_event(OrdenCreada {
+                orden_id: id,
+                comprador: nueva_orden.comprador,
+                vendedor: nueva_orden.vendedor,
+                lineas: nueva_orden.lineas.clone(),
+                monto: nueva_orden.monto,
+                moneda: nueva_orden.moneda,
+            });
+            Ok(id)
+        }
+
+        /// Calcula los ids de una página y el cursor siguiente, dado el total de ids asignados.
+        fn rango_pagina(desde: u32, limite: u32, total: u32) -> (core::ops::Range<u32>, Option<u32>) {
+            let inicio = desde.min(total);
+            let fin = inicio.saturating_add(limite.min(LIMITE_PAGINA)).min(total);
+            let siguiente = if fin < total { Some(fin) } else { None };
+            (inicio..fin, siguiente)
+        }
+
+        /// Agrega un producto al índice de su vendedor.
+        fn indexar_producto(&mut self, vendedor: AccountId, id: u32) {
+            Self::indexar(&mut self.productos_por_vendedor, &mut self.cantidad_productos_por_vendedor, vendedor, id);
+        }
+
+        /// Agrega una orden a los índices de su comprador y de su vendedor.
+        fn indexar_orden(&mut self, comprador: AccountId, vendedor: AccountId, id: u32) {
+            Self::indexar(&mut self.ordenes_por_comprador, &mut self.cantidad_ordenes_por_comprador, comprador, id);
+            Self::indexar(&mut self.ordenes_por_vendedor, &mut self.cantidad_ordenes_por_vendedor, vendedor, id);
+        }
+
+        /// Agrega un id al final del índice secundario de una cuenta.
+        fn indexar<KI: StorageKey, KC: StorageKey>(
+            indice: &mut Mapping<(AccountId, u32), u32, KI>,
+            cantidad: &mut Mapping<AccountId, u32, KC>,
+            cuenta: AccountId,
+            id: u32,
+        ) {
+            let posicion = cantidad.get(cuenta).unwrap_or(0);
+            indice.insert((cuenta, posicion), &id);
+            cantidad.insert(cuenta, &posicion.saturating_add(1));
+        }
+
+        /// Obtiene una copia de una orden por su id.
+        fn buscar_orden(&self, id: u32) -> Result<Orden, SistemaError> {
+            self.ordenes.get(id).ok_or(SistemaError::OrdenNoExiste)
+        }
+
+        /// Verifica si el caller tiene permiso para cambiar el estado de la orden.
+        fn verificar_permiso_orden(
+            &self,
+            caller: AccountId,
+            orden: &Orden,
+            nuevo_estado: &EstadoOrden
+        ) -> Result<(), SistemaError> {
+            match nuevo_estado {
+                EstadoOrden::Enviada if caller != orden.vendedor => Err(SistemaError::NoEsRolCorrecto),
+                EstadoOrden::Recibida if caller != orden.comprador => Err(SistemaError::NoEsRolCorrecto),
+                _ => self.verificar_transicion_estado(&orden.estado, nuevo_estado),
+            }
+        }
+
+        /// Verifica que el caller sea el comprador o el vendedor de la orden.
+        fn verificar_parte_de_orden(&self, caller: AccountId, orden: &Orden) -> Result<(), SistemaError> {
+            if caller != orden.comprador && caller != orden.vendedor {
+                Err(SistemaError::NoEsRolCorrecto)
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Verifica que comprador y vendedor sean cuentas distintas, para que nadie
+        /// pueda comprarse a sí mismo ni sumar reputación con órdenes propias.
+        fn verificar_no_es_autocompra(&self, comprador: AccountId, vendedor: AccountId) -> Result<(), SistemaError> {
+            if comprador == vendedor {
+                Err(SistemaError::AutoCompraNoPermitida)
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Verifica que el puntaje esté dentro del rango permitido (1 a 5).
+        fn verificar_puntaje(&self, puntaje: u8) -> Result<(), SistemaError> {
+            if !(PUNTAJE_MINIMO..=PUNTAJE_MAXIMO).contains(&puntaje) {
+                Err(SistemaError::PuntajeInvalido)
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Verifica que la orden esté recibida y que la parte todavía no haya calificado.
+        fn verificar_orden_calificable(&self, orden: &Orden, ya_califico: bool) -> Result<(), SistemaError> {
+            if orden.estado != EstadoOrden::Recibida {
+                Err(SistemaError::OrdenNoRecibida)
+            } else if ya_califico {
+                Err(SistemaError::YaCalificado)
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Verifica que la transición de estado de la orden sea válida.
+        fn verificar_transicion_estado(
+            &self,
+            actual: &EstadoOrden,
+            nuevo: &EstadoOrden
+        ) -> Result<(), SistemaError> {
+            match (actual, nuevo) {
+                (EstadoOrden::Pendiente, EstadoOrden::Enviada) => Ok(()),
+                (EstadoOrden::Enviada, EstadoOrden::Recibida) => Ok(()),
+                (EstadoOrden::Pendiente, EstadoOrden::Cancelada) => Ok(()),
+                (EstadoOrden::Enviada, EstadoOrden::EnDisputa) => Ok(()),
+                _ => Err(SistemaError::EstadoInvalido),
+            }
+        }
+    }
+
+    // ────────────────
+    // CONSTANTES
+    // ────────────────
+
+    /// Puntaje mínimo de una calificación.
+    pub const PUNTAJE_MINIMO: u8 = 1;
+    /// Puntaje máximo de una calificación.
+    pub const PUNTAJE_MAXIMO: u8 = 5;
+    /// Factor de escala de los promedios de reputación (dos decimales).
+    pub const ESCALA_PROMEDIO: u32 = 100;
+    /// Cantidad máxima de elementos devueltos por una consulta paginada.
+    pub const LIMITE_PAGINA: u32 = 50;
+    /// Cantidad máxima de líneas de un carrito en `crear_orden_multiple`.
+    pub const LIMITE_CARRITO: u32 = 20;
+    /// Plazo que tiene el vendedor para enviar una orden desde su creación (7 días, en milisegundos).
+    pub const PLAZO_ENVIO: Timestamp = 7 * 24 * 60 * 60 * 1000;
+    /// Plazo que tiene el comprador para confirmar la recepción desde el envío (14 días, en milisegundos).
+    pub const PLAZO_CONFIRMACION: Timestamp = 14 * 24 * 60 * 60 * 1000;
+    /// Versión del formato de almacenamiento de este código. Debe incrementarse
+    /// cada vez que cambia la codificación de `Usuario`, `Producto`, `Orden` o de
+    /// los índices por cuenta, agregando en `migrar` el paso que convierte los
+    /// registros existentes.
+    pub const VERSION_ALMACENAMIENTO: u32 = 6;
+    /// Puntos básicos equivalentes al 100% de un monto.
+    pub const BPS_TOTAL: u16 = 10_000;
+    /// Comisión máxima que puede fijar el propietario (10%).
+    pub const COMISION_MAXIMA_BPS: u16 = 1_000;
+
+    // ────────────────
+    // EVENTOS
+    // ────────────────
+
+    /// Se emite cuando una cuenta se registra en el marketplace.
+    #[ink(event)]
+    pub struct UsuarioRegistrado {
+        #[ink(topic)]
+        pub usuario: AccountId,
+        pub rol: RolUsuario,
+    }
+
+    /// Se emite cuando un usuario cambia de rol.
+    #[ink(event)]
+    pub struct RolModificado {
+        #[ink(topic)]
+        pub usuario: AccountId,
+        /// Rol anterior, o `None` si el usuario no podía comprar ni vender.
+        pub rol_anterior: Option<RolUsuario>,
+        pub rol_nuevo: RolUsuario,
+    }
+
+    /// Se emite cuando un moderador otorga o revoca un permiso.
+    #[ink(event)]
+    pub struct PermisoActualizado {
+        #[ink(topic)]
+        pub usuario: AccountId,
+        pub permiso: Permiso,
+        /// `true` si el permiso se otorgó, `false` si se revocó.
+        pub otorgado: bool,
+        pub moderador: AccountId,
+    }
+
+    /// Se emite cuando un vendedor publica un producto.
+    #[ink(event)]
+    pub struct ProductoPublicado {
+        #[ink(topic)]
+        pub producto_id: u32,
+        #[ink(topic)]
+        pub vendedor: AccountId,
+        pub precio: Balance,
+        pub moneda: Moneda,
+        pub cantidad: u32,
+        pub categoria: String,
+    }
+
+    /// Se emite cada vez que cambia el stock de un producto.
+    #[ink(event)]
+    pub struct StockActualizado {
+        #[ink(topic)]
+        pub producto_id: u32,
+        /// Stock disponible luego del cambio.
+        pub stock: u32,
+    }
+
+    /// Se emite cuando el vendedor edita un producto.
+    #[ink(event)]
+    pub struct ProductoEditado {
+        #[ink(topic)]
+        pub producto_id: u32,
+        /// Precio luego de la edición.
+        pub precio: Balance,
+    }
+
+    /// Se emite cuando un producto se pausa, se reactiva o se da de baja.
+    #[ink(event)]
+    pub struct EstadoProductoActualizado {
+        #[ink(topic)]
+        pub producto_id: u32,
+        pub estado_anterior: EstadoProducto,
+        pub estado_nuevo: EstadoProducto,
+    }
+
+    /// Se emite cuando se crea una orden de compra.
+    #[ink(event)]
+    pub struct OrdenCreada {
+        #[ink(topic)]
+        pub orden_id: u32,
+        #[ink(topic)]
+        pub comprador: AccountId,
+        #[ink(topic)]
+        pub vendedor: AccountId,
+        pub lineas: Vec<LineaOrden>,
+        pub monto: Balance,
+        pub moneda: Moneda,
+    }
+
+    /// Se emite en cada transición de estado de una orden.
+    #[ink(event)]
+    pub struct EstadoOrdenActualizado {
+        #[ink(topic)]
+        pub orden_id: u32,
+        pub estado_anterior: EstadoOrden,
+        pub estado_nuevo: EstadoOrden,
+    }
+
+    /// Se emite cuando una de las partes solicita cancelar una orden.
+    #[ink(event)]
+    pub struct CancelacionSolicitada {
+        #[ink(topic)]
+        pub orden_id: u32,
+        #[ink(topic)]
+        pub solicitante: AccountId,
+    }
+
+    /// Se emite cuando una de las partes califica a la otra.
+    #[ink(event)]
+    pub struct CalificacionRegistrada {
+        #[ink(topic)]
+        pub orden_id: u32,
+        pub calificador: AccountId,
+        #[ink(topic)]
+        pub calificado: AccountId,
+        pub puntaje: u8,
+    }
+
+    /// Se emite cuando el propietario transfiere la propiedad del contrato.
+    #[ink(event)]
+    pub struct PropiedadTransferida {
+        #[ink(topic)]
+        pub propietario_anterior: AccountId,
+        #[ink(topic)]
+        pub propietario_nuevo: AccountId,
+    }
+
+    /// Se emite cuando el propietario agrega o quita un administrador.
+    #[ink(event)]
+    pub struct AdministradorActualizado {
+        #[ink(topic)]
+        pub cuenta: AccountId,
+        /// `true` si la cuenta se agregó, `false` si se quitó.
+        pub habilitado: bool,
+    }
+
+    /// Se emite cuando se pausa el contrato.
+    #[ink(event)]
+    pub struct Pausado {
+        #[ink(topic)]
+        pub cuenta: AccountId,
+    }
+
+    /// Se emite cuando se reanuda el contrato.
+    #[ink(event)]
+    pub struct Reanudado {
+        #[ink(topic)]
+        pub cuenta: AccountId,
+    }
+
+    /// Se emite cuando el propietario reemplaza el código del contrato.
+    #[ink(event)]
+    pub struct CodigoActualizado {
+        #[ink(topic)]
+        pub code_hash: Hash,
+    }
+
+    /// Se emite cuando se migra el almacenamiento a una nueva versión.
+    #[ink(event)]
+    pub struct AlmacenamientoMigrado {
+        pub version_anterior: u32,
+        pub version_nueva: u32,
+    }
+
+    /// Se emite cuando el propietario cambia la comisión del marketplace.
+    #[ink(event)]
+    pub struct ComisionActualizada {
+        pub comision_bps: u16,
+    }
+
+    /// Se emite cuando el propietario retira comisiones de la tesorería.
+    #[ink(event)]
+    pub struct ComisionesRetiradas {
+        #[ink(topic)]
+        pub destino: AccountId,
+        pub moneda: Moneda,
+        pub monto: Balance,
+    }
+
+    /// Se emite cuando una cuenta retira fondos de su saldo.
+    #[ink(event)]
+    pub struct FondosRetirados {
+        #[ink(topic)]
+        pub cuenta: AccountId,
+        pub moneda: Moneda,
+        pub monto: Balance,
+    }
+
+    /// Se emite cuando el propietario agrega o quita un token de la lista de permitidos.
+    #[ink(event)]
+    pub struct TokenActualizado {
+        #[ink(topic)]
+        pub token: AccountId,
+        /// `true` si el token se permitió, `false` si se quitó.
+        pub permitido: bool,
+    }
+
+    // ────────────────
+    // ENUMS
+    // ────────────────
+
+    /// Enum para los roles posibles de un usuario.
+    ///
+    /// Cada rol es un atajo para un conjunto de permisos (ver `Permisos::de_rol`).
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum RolUsuario {
+        Comprador,
+        Vendedor,
+        Ambos,
+    }
+
+    /// Capacidad que puede otorgarse o revocarse a un usuario de forma individual.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum Permiso {
+        /// Crear órdenes de compra.
+        Comprar,
+        /// Publicar y administrar productos.
+        Vender,
+        /// Resolver disputas.
+        Arbitrar,
+        /// Otorgar y revocar permisos a otros usuarios.
+        Moderar,
+    }
+
+    impl Permiso {
+        /// Bit que ocupa el permiso dentro de `Permisos`.
+        fn bit(self) -> u8 {
+            1 << self as u8
+        }
+    }
+
+    /// Conjunto de permisos de un usuario, guardado como máscara de bits.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Permisos(u8);
+
+    impl Permisos {
+        /// Permisos que otorga cada rol al registrarse.
+        pub fn de_rol(rol: &RolUsuario) -> Self {
+            let mut permisos = Self::default();
+            match rol {
+                RolUsuario::Comprador => permisos.otorgar(Permiso::Comprar),
+                RolUsuario::Vendedor => permisos.otorgar(Permiso::Vender),
+                RolUsuario::Ambos => {
+                    permisos.otorgar(Permiso::Comprar);
+                    permisos.otorgar(Permiso::Vender);
+                }
+            }
+            permisos
+        }
+
+        /// Indica si el conjunto incluye el permiso.
+        pub fn contiene(&self, permiso: Permiso) -> bool {
+            self.0 & permiso.bit() != 0
+        }
+
+        /// Agrega un permiso al conjunto.
+        pub fn otorgar(&mut self, permiso: Permiso) {
+            self.0 |= permiso.bit();
+        }
+
+        /// Quita un permiso del conjunto.
+        pub fn revocar(&mut self, permiso: Permiso) {
+            self.0 &= !permiso.bit();
+        }
+
+        /// Agrega todos los permisos de otro conjunto.
+        pub fn agregar(&mut self, otros: Permisos) {
+            self.0 |= otros.0;
+        }
+
+        /// Indica si ambos conjuntos tienen al menos un permiso en común.
+        pub fn comparte_alguno(&self, otros: Permisos) -> bool {
+            self.0 & otros.0 != 0
+        }
+
+        /// Rol equivalente a los permisos de compra y venta, o `None` si no tiene ninguno.
+        pub fn rol(&self) -> Option<RolUsuario> {
+            match (self.contiene(Permiso::Comprar), self.contiene(Permiso::Vender)) {
+                (true, true) => Some(RolUsuario::Ambos),
+                (true, false) => Some(RolUsuario::Comprador),
+                (false, true) => Some(RolUsuario::Vendedor),
+                (false, false) => None,
+            }
+        }
+    }
+
+    /// Enum para los posibles estados de una orden.
+#[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]    pub enum EstadoOrden {
+        Pendiente,
+        Enviada,
+        Recibida,
+        Cancelada,
+        EnDisputa,
+    }
+
+    /// Enum para los posibles estados de un producto publicado.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum EstadoProducto {
+        /// Acepta órdenes de compra.
+        Activo,
+        /// No acepta órdenes hasta que el vendedor lo reactive.
+        Pausado,
+        /// Retirado del catálogo de forma definitiva.
+        DadoDeBaja,
+    }
+
+    /// Moneda en la que se expresa el precio de un producto y se paga una orden.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum Moneda {
+        /// Token nativo de la cadena.
+        #[default]
+        Nativa,
+        /// Token PSP22 permitido por el propietario, identificado por su contrato.
+        Psp22(AccountId),
+    }
+
+    /// Enum para las posibles resoluciones de una disputa.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum ResolucionDisputa {
+        /// Se devuelve todo el pago al comprador.
+        ReembolsarComprador,
+        /// Se acredita todo el pago al vendedor.
+        PagarVendedor,
+        /// Se reparte el pago: el comprador recibe `monto_comprador` y el vendedor el resto.
+        Dividir { monto_comprador: Balance },
+    }
+
+    // ────────────────
+    // ERRORES DEL SISTEMA
+    // ────────────────
+
+    /// Enum para los posibles errores del sistema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]    pub enum SistemaError {
+        CantidadInsuficiente,
+        UsuarioNoRegistrado,
+        ProductosVacios,
+        NoEsRolCorrecto,
+        EstadoInvalido,
+        OrdenNoExiste,
+        UsuarioExistente,
+        StockInsuficiente,
+        CancelacionYaSolicitada,
+        CancelacionNoSolicitada,
+        PuntajeInvalido,
+        OrdenNoRecibida,
+        YaCalificado,
+        PagoIncorrecto,
+        TransferenciaFallida,
+        NoEsArbitro,
+        MontoInvalido,
+        LimiteAlcanzado,
+        ProductoNoDisponible,
+        AutoCompraNoPermitida,
+        NoEsPropietario,
+        NoEsAdministrador,
+        ContratoPausado,
+        ContratoNoPausado,
+        CodigoInvalido,
+        MigracionPendiente,
+        SinMigracionPendiente,
+        ComisionInvalida,
+        ComisionesInsuficientes,
+        SaldoInsuficiente,
+        TokenNoPermitido,
+        CarritoInvalido,
+        PlazoNoVencido,
+        PermisoBloqueado,
+        CantidadExcedida,
+    }
+    impl core::fmt::Display for SistemaError {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            match self {
+                SistemaError::CantidadInsuficiente => write!(f, "Cantidad insuficiente"),
+                SistemaError::UsuarioNoRegistrado => write!(f, "El usuario no se encuentra registrado"),
+                SistemaError::NoEsRolCorrecto => write!(f, "El usuario no es del rol correcto"),
+                SistemaError::ProductosVacios => write!(f, "No se encontraron productos"),
+                SistemaError::EstadoInvalido => write!(f, "El estado de la orden es inválido"),
+                SistemaError::OrdenNoExiste => write!(f, "La orden no existe"),
+                SistemaError::UsuarioExistente => write!(f, "El usuario ya está registrado"),
+                SistemaError::StockInsuficiente => write!(f, "Stock insuficiente para la cantidad solicitada"),
+                SistemaError::CancelacionYaSolicitada => write!(f, "Ya existe una solicitud de cancelación para la orden"),
+                SistemaError::CancelacionNoSolicitada => write!(f, "La contraparte no solicitó la cancelación de la orden"),
+                SistemaError::PuntajeInvalido => write!(f, "La calificación debe ser un entero entre 1 y 5"),
+                SistemaError::OrdenNoRecibida => write!(f, "Solo se pueden calificar órdenes recibidas"),
+                SistemaError::YaCalificado => write!(f, "Ya se calificó esta orden"),
+                SistemaError::PagoIncorrecto => write!(f, "El pago no coincide con el precio total de la orden"),
+                SistemaError::TransferenciaFallida => write!(f, "No se pudo transferir el pago retenido"),
+                SistemaError::NoEsArbitro => write!(f, "La cuenta no está habilitada como árbitro"),
+                SistemaError::MontoInvalido => write!(f, "El monto supera el pago retenido de la orden"),
+                SistemaError::LimiteAlcanzado => write!(f, "Se alcanzó la cantidad máxima de ids disponibles"),
+                SistemaError::ProductoNoDisponible => write!(f, "El producto no está disponible"),
+                SistemaError::AutoCompraNoPermitida => write!(f, "Comprador y vendedor no pueden ser la misma cuenta"),
+                SistemaError::NoEsPropietario => write!(f, "Solo el propietario del contrato puede realizar esta operación"),
+                SistemaError::NoEsAdministrador => write!(f, "La cuenta no está habilitada como administradora"),
+                SistemaError::ContratoPausado => write!(f, "El contrato está pausado"),
+                SistemaError::ContratoNoPausado => write!(f, "El contrato no está pausado"),
+                SistemaError::CodigoInvalido => write!(f, "No existe código subido con ese hash"),
+                SistemaError::MigracionPendiente => write!(f, "El almacenamiento debe migrarse antes de operar"),
+                SistemaError::SinMigracionPendiente => write!(f, "El almacenamiento ya está en la versión actual"),
+                SistemaError::ComisionInvalida => write!(f, "La comisión supera el máximo permitido"),
+                SistemaError::ComisionesInsuficientes => write!(f, "El monto supera las comisiones acumuladas"),
+                SistemaError::SaldoInsuficiente => write!(f, "El monto supera el saldo disponible para retirar"),
+                SistemaError::TokenNoPermitido => write!(f, "El token no está habilitado como moneda del marketplace"),
+                SistemaError::CarritoInvalido => write!(f, "El carrito debe tener entre 1 y {} líneas", LIMITE_CARRITO),
+                SistemaError::PlazoNoVencido => write!(f, "Todavía no venció el plazo de la orden"),
+                SistemaError::PermisoBloqueado => write!(f, "Un moderador revocó un permiso que incluye el rol pedido"),
+                SistemaError::CantidadExcedida => write!(f, "La cantidad pedida de un producto supera el máximo representable"),
+            }
+        }
+    }
+
+    // ────────────────
+    // ESTRUCTURAS PRINCIPALES
+    // ────────────────
+
+    /// Representa un usuario del marketplace.
+    
+#[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]    pub struct Usuario {
+        /// Dirección de la cuenta del usuario.
+        pub direccion: AccountId,
+        /// Permisos del usuario.
+        pub permisos: Permisos,
+        /// Reputación como comprador.
+        pub reputacion_como_comprador: Reputacion,
+        /// Reputación como vendedor.
+        pub reputacion_como_vendedor: Reputacion,
+    }
+
+    impl Usuario {
+        /// Crea un usuario con los permisos indicados y sin calificaciones.
+        pub fn new(direccion: AccountId, permisos: Permisos) -> Self {
+            Self {
+                direccion,
+                permisos,
+                reputacion_como_comprador: Reputacion::default(),
+                reputacion_como_vendedor: Reputacion::default(),
+            }
+        }
+
+        /// Rol equivalente a los permisos de compra y venta del usuario.
+        pub fn rol(&self) -> Option<RolUsuario> {
+            self.permisos.rol()
+        }
+    }
+
+    /// Reputación acumulada de un usuario en uno de sus roles.
+    #[derive(Debug, Clone, PartialEq, Eq, Default)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Reputacion {
+        /// Suma de todos los puntajes recibidos.
+        pub total_puntos: u32,
+        /// Cantidad de calificaciones recibidas.
+        pub cantidad_calificaciones: u32,
+        /// Cantidad de calificaciones por puntaje (posición 0 = 1 estrella).
+        pub histograma: [u32; 5],
+    }
+    impl Reputacion {
+        /// Registra una calificación ya validada (entre 1 y 5).
+        pub fn registrar(&mut self, puntaje: u8) {
+            self.total_puntos = self.total_puntos.saturating_add(puntaje as u32);
+            self.cantidad_calificaciones = self.cantidad_calificaciones.saturating_add(1);
+            if let Some(casillero) = self.histograma.get_mut(puntaje.saturating_sub(PUNTAJE_MINIMO) as usize) {
+                *casillero = casillero.saturating_add(1);
+            }
+        }
+
+        /// Promedio de las calificaciones escalado por `ESCALA_PROMEDIO`, 0 si no hay calificaciones.
+        pub fn promedio(&self) -> u32 {
+            if self.cantidad_calificaciones == 0 {
+                return 0;
+            }
+            let escalado = (self.total_puntos as u64).saturating_mul(ESCALA_PROMEDIO as u64);
+            (escalado / self.cantidad_calificaciones as u64) as u32
+        }
+    }
+
+    /// Resumen público de la reputación de un usuario.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct ResumenReputacion {
+        /// Promedio como comprador, escalado por `ESCALA_PROMEDIO`.
+        pub promedio_como_comprador: u32,
+        /// Cantidad de calificaciones recibidas como comprador.
+        pub calificaciones_como_comprador: u32,
+        /// Promedio como vendedor, escalado por `ESCALA_PROMEDIO`.
+        pub promedio_como_vendedor: u32,
+        /// Cantidad de calificaciones recibidas como vendedor.
+        pub calificaciones_como_vendedor: u32,
+    }
+
+    /// Avance de una migración del almacenamiento repartida en varias llamadas a `migrar`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct ProgresoMigracion {
+        /// Versión a la que lleva el paso en curso.
+        pub paso: u32,
+        /// Próxima posición del paso en curso que falta convertir.
+        pub posicion: u32,
+    }
+
+    /// Página de resultados de una consulta paginada.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct Pagina<T> {
+        /// Elementos de la página, ordenados por id.
+        pub elementos: Vec<T>,
+        /// Id desde el cual pedir la próxima página, o `None` si no quedan más.
+        pub siguiente: Option<u32>,
+    }
+
+    /// Representa un producto publicado en el marketplace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]    pub struct Producto {
+        /// Identificador único del producto.
+        pub id: u32,
+        /// Nombre del producto.
+        pub nombre: String,
+        /// Descripción del producto.
+        pub descripcion: String,
+        /// Precio del producto.
+        pub precio: Balance,
+        /// Cantidad disponible.
+        pub cantidad: u32,
+        /// Categoría del producto.
+        pub categoria: String,
+        /// Dirección del vendedor.
+        pub vendedor: AccountId,
+        /// Unidades vendidas en órdenes que llegaron a `Recibida`.
+        pub unidades_vendidas: u32,
+        /// Estado de la publicación.
+        pub estado: EstadoProducto,
+        /// Moneda en la que está expresado el precio.
+        pub moneda: Moneda,
+    }
+    impl Producto {
+        /// Crea una nueva instancia de Producto.
+        pub fn new(id: u32, nombre: String, descripcion: String, precio: Balance, cantidad: u32, categoria: String, vendedor: AccountId) -> Self {
+            Self {
+                id,
+                nombre,
+                descripcion,
+                precio,
+                cantidad,
+                categoria,
+                vendedor,
+                unidades_vendidas: 0,
+                estado: EstadoProducto::Activo,
+                moneda: Moneda::Nativa,
+            }
+        }
+    }
+
+    /// Representa una orden de compra.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]    
+    pub struct Orden {
+        /// Identificador único de la orden.
+        pub id: u32,
+        /// Dirección del comprador.
+        pub comprador: AccountId,
+        /// Dirección del vendedor.
+        pub vendedor: AccountId,
+        /// Productos comprados, todos del mismo vendedor y en la misma moneda.
+        pub lineas: Vec<LineaOrden>,
+        /// Monto pagado por el comprador y retenido por el contrato.
+        pub monto: Balance,
+        /// Estado actual de la orden.
+        pub estado: EstadoOrden,
+        /// Indica si el comprador calificó.
+        pub comprador_califico: bool,
+        /// Puntaje que el comprador le dio al vendedor en esta orden, si ya calificó.
+        pub puntaje_al_vendedor: Option<u8>,
+        /// Indica si el vendedor calificó.
+        pub vendedor_califico: bool,
+        /// Indica si el comprador dio su consentimiento para cancelar.
+        pub comprador_acepta_cancelacion: bool,
+        /// Indica si el vendedor dio su consentimiento para cancelar.
+        pub vendedor_acepta_cancelacion: bool,
+        /// Disputa abierta sobre la orden, si la hubo.
+        pub disputa: Option<Disputa>,
+        /// Moneda en la que se pagó la orden.
+        pub moneda: Moneda,
+        /// Momento de creación de la orden, según el timestamp del bloque.
+        pub creada_en: Timestamp,
+        /// Momento en que el vendedor marcó la orden como enviada.
+        pub enviada_en: Option<Timestamp>,
+        /// Comisión que el marketplace descontó al liquidar el pago al vendedor.
+        pub comision: Balance,
+    }
+    impl Orden {
+        /// Crea una nueva instancia de Orden de un solo producto.
+        pub fn new(id: u32, comprador: AccountId, vendedor: AccountId, producto_id: u32, cantidad: u32, monto: Balance) -> Self {
+            Self::con_lineas(id, comprador, vendedor, ink::prelude::vec![LineaOrden { producto_id, cantidad, monto }])
+        }
+
+        /// Crea una orden con varios productos; el monto es la suma de las líneas.
+        pub fn con_lineas(id: u32, comprador: AccountId, vendedor: AccountId, lineas: Vec<LineaOrden>) -> Self {
+            let monto = lineas.iter().fold(0, |total: Balance, linea| total.saturating_add(linea.monto));
+            Self {
+                id,
+                comprador,
+                vendedor,
+                lineas,
+                monto,
+                estado: EstadoOrden::Pendiente,
+                comprador_califico: false,
+                puntaje_al_vendedor: None,
+                vendedor_califico: false,
+                comprador_acepta_cancelacion: false,
+                vendedor_acepta_cancelacion: false,
+                disputa: None,
+                moneda: Moneda::Nativa,
+                creada_en: 0,
+                enviada_en: None,
+                comision: 0,
+            }
+        }
+    }
+
+    /// Un producto comprado dentro de una orden.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct LineaOrden {
+        /// Identificador del producto comprado.
+        pub producto_id: u32,
+        /// Cantidad comprada.
+        pub cantidad: u32,
+        /// Parte del monto de la orden que corresponde a esta línea.
+        pub monto: Balance,
+    }
+
+    /// Formato de los registros en la versión 1 del almacenamiento, usado solo por `migrar`.
+    mod v1 {
+        use super::{AccountId, Balance, Disputa, EstadoOrden, EstadoProducto, String};
+
+        #[ink::scale_derive(Encode, Decode)]
+        pub struct Producto {
+            pub id: u32,
+            pub nombre: String,
+            pub descripcion: String,
+            pub precio: Balance,
+            pub cantidad: u32,
+            pub categoria: String,
+            pub vendedor: AccountId,
+            pub unidades_vendidas: u32,
+            pub estado: EstadoProducto,
+        }
+
+        #[ink::scale_derive(Encode, Decode)]
+        pub struct Orden {
+            pub id: u32,
+            pub comprador: AccountId,
+            pub vendedor: AccountId,
+            pub producto_id: u32,
+            pub cantidad: u32,
+            pub monto: Balance,
+            pub estado: EstadoOrden,
+            pub comprador_califico: bool,
+            pub puntaje_al_vendedor: Option<u8>,
+            pub vendedor_califico: bool,
+            pub comprador_acepta_cancelacion: bool,
+            pub vendedor_acepta_cancelacion: bool,
+            pub disputa: Option<Disputa>,
+        }
+    }
+
+    impl From<v1::Producto> for Producto {
+        fn from(anterior: v1::Producto) -> Self {
+            Self {
+                id: anterior.id,
+                nombre: anterior.nombre,
+                descripcion: anterior.descripcion,
+                precio: anterior.precio,
+                cantidad: anterior.cantidad,
+                categoria: anterior.categoria,
+                vendedor: anterior.vendedor,
+                unidades_vendidas: anterior.unidades_vendidas,
+                estado: anterior.estado,
+                moneda: Moneda::Nativa,
+            }
+        }
+    }
+
+    /// Formato de los registros en la versión 2 del almacenamiento, usado solo por `migrar`.
+    mod v2 {
+        use super::{AccountId, Balance, Disputa, EstadoOrden, Moneda};
+
+        #[ink::scale_derive(Encode, Decode)]
+        pub struct Orden {
+            pub id: u32,
+            pub comprador: AccountId,
+            pub vendedor: AccountId,
+            pub producto_id: u32,
+            pub cantidad: u32,
+            pub monto: Balance,
+            pub estado: EstadoOrden,
+            pub comprador_califico: bool,
+            pub puntaje_al_vendedor: Option<u8>,
+            pub vendedor_califico: bool,
+            pub comprador_acepta_cancelacion: bool,
+            pub vendedor_acepta_cancelacion: bool,
+            pub disputa: Option<Disputa>,
+            pub moneda: Moneda,
+        }
+    }
+
+    /// Formato de los registros en la versión 3 del almacenamiento, usado solo por `migrar`.
+    mod v3 {
+        use super::{AccountId, Balance, Disputa, EstadoOrden, LineaOrden, Moneda, Vec};
+
+        #[ink::scale_derive(Encode, Decode)]
+        pub struct Orden {
+            pub id: u32,
+            pub comprador: AccountId,
+            pub vendedor: AccountId,
+            pub lineas: Vec<LineaOrden>,
+            pub monto: Balance,
+            pub estado: EstadoOrden,
+            pub comprador_califico: bool,
+            pub puntaje_al_vendedor: Option<u8>,
+            pub vendedor_califico: bool,
+            pub comprador_acepta_cancelacion: bool,
+            pub vendedor_acepta_cancelacion: bool,
+            pub disputa: Option<Disputa>,
+            pub moneda: Moneda,
+        }
+    }
+
+    /// Formato de los registros en las versiones 4 y 5 del almacenamiento, usado solo por `migrar`.
+    mod v5 {
+        use super::{AccountId, Balance, Disputa, EstadoOrden, LineaOrden, Moneda, Timestamp, Vec};
+
+        #[ink::scale_derive(Encode, Decode)]
+        pub struct Orden {
+            pub id: u32,
+            pub comprador: AccountId,
+            pub vendedor: AccountId,
+            pub lineas: Vec<LineaOrden>,
+            pub monto: Balance,
+            pub estado: EstadoOrden,
+            pub comprador_califico: bool,
+            pub puntaje_al_vendedor: Option<u8>,
+            pub vendedor_califico: bool,
+            pub comprador_acepta_cancelacion: bool,
+            pub vendedor_acepta_cancelacion: bool,
+            pub disputa: Option<Disputa>,
+            pub moneda: Moneda,
+            pub creada_en: Timestamp,
+            pub enviada_en: Option<Timestamp>,
+        }
+    }
+
+    impl From<v5::Orden> for Orden {
+        fn from(anterior: v5::Orden) -> Self {
+            Self {
+                id: anterior.id,
+                comprador: anterior.comprador,
+                vendedor: anterior.vendedor,
+                lineas: anterior.lineas,
+                monto: anterior.monto,
+                estado: anterior.estado,
+                comprador_califico: anterior.comprador_califico,
+                puntaje_al_vendedor: anterior.puntaje_al_vendedor,
+                vendedor_califico: anterior.vendedor_califico,
+                comprador_acepta_cancelacion: anterior.comprador_acepta_cancelacion,
+                vendedor_acepta_cancelacion: anterior.vendedor_acepta_cancelacion,
+                disputa: anterior.disputa,
+                moneda: anterior.moneda,
+                creada_en: anterior.creada_en,
+                enviada_en: anterior.enviada_en,
+                comision: 0,
+            }
+        }
+    }
+
+    impl From<v3::Orden> for v5::Orden {
+        fn from(anterior: v3::Orden) -> Self {
+            Self {
+                id: anterior.id,
+                comprador: anterior.comprador,
+                vendedor: anterior.vendedor,
+                lineas: anterior.lineas,
+                monto: anterior.monto,
+                estado: anterior.estado,
+                comprador_califico: anterior.comprador_califico,
+                puntaje_al_vendedor: anterior.puntaje_al_vendedor,
+                vendedor_califico: anterior.vendedor_califico,
+                comprador_acepta_cancelacion: anterior.comprador_acepta_cancelacion,
+                vendedor_acepta_cancelacion: anterior.vendedor_acepta_cancelacion,
+                disputa: anterior.disputa,
+                moneda: anterior.moneda,
+                creada_en: 0,
+                enviada_en: None,
+            }
+        }
+    }
+
+    impl From<v2::Orden> for v3::Orden {
+        fn from(anterior: v2::Orden) -> Self {
+            Self {
+                id: anterior.id,
+                comprador: anterior.comprador,
+                vendedor: anterior.vendedor,
+                lineas: ink::prelude::vec![LineaOrden {
+                    producto_id: anterior.producto_id,
+                    cantidad: anterior.cantidad,
+                    monto: anterior.monto,
+                }],
+                monto: anterior.monto,
+                estado: anterior.estado,
+                comprador_califico: anterior.comprador_califico,
+                puntaje_al_vendedor: anterior.puntaje_al_vendedor,
+                vendedor_califico: anterior.vendedor_califico,
+                comprador_acepta_cancelacion: anterior.comprador_acepta_cancelacion,
+                vendedor_acepta_cancelacion: anterior.vendedor_acepta_cancelacion,
+                disputa: anterior.disputa,
+                moneda: anterior.moneda,
+            }
+        }
+    }
+
+    impl From<v1::Orden> for v2::Orden {
+        fn from(anterior: v1::Orden) -> Self {
+            Self {
+                id: anterior.id,
+                comprador: anterior.comprador,
+                vendedor: anterior.vendedor,
+                producto_id: anterior.producto_id,
+                cantidad: anterior.cantidad,
+                monto: anterior.monto,
+                estado: anterior.estado,
+                comprador_califico: anterior.comprador_califico,
+                puntaje_al_vendedor: anterior.puntaje_al_vendedor,
+                vendedor_califico: anterior.vendedor_califico,
+                comprador_acepta_cancelacion: anterior.comprador_acepta_cancelacion,
+                vendedor_acepta_cancelacion: anterior.vendedor_acepta_cancelacion,
+                disputa: anterior.disputa,
+                moneda: Moneda::Nativa,
+            }
+        }
+    }
+
+    /// Representa una disputa abierta sobre una orden enviada.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Disputa {
+        /// Parte que abrió la disputa.
+        pub iniciador: AccountId,
+        /// Hash del documento que describe el motivo del reclamo.
+        pub motivo_hash: Hash,
+        /// Resolución del árbitro, `None` mientras la disputa siga abierta.
+        pub resolucion: Option<ResolucionDisputa>,
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test;
+        use parity_scale_codec::Decode;
+
+        // Función auxiliar para crear un contrato con un vendedor registrado y caller seteado
+        fn setup_contract_con_vendedor() -> MarketplacePrincipal {
+            // El contrato usa una cuenta propia, distinta de la del vendedor (que coincide con Alice),
+            // con el balance mínimo exigido por el entorno de pruebas
+            let cuenta_contrato = AccountId::from([0xCC; 32]);
+            test::set_callee::<ink::env::DefaultEnvironment>(cuenta_contrato);
+            test::set_account_balance::<ink::env::DefaultEnvironment>(cuenta_contrato, 1_000_000);
+            let mut contrato = MarketplacePrincipal::new();
+            let caller = AccountId::from([0x01; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+            contrato.registrar_usuario(RolUsuario::Vendedor).unwrap();
+            contrato
+        }
+        
+        // Función auxiliar que simula el pago adjunto a la llamada: fija el valor transferido
+        // y lo acredita en el balance del contrato, como haría la cadena
+        fn pagar(monto: Balance) {
+            let contrato = test::callee::<ink::env::DefaultEnvironment>();
+            let saldo = test::get_account_balance::<ink::env::DefaultEnvironment>(contrato).unwrap_or(0);
+            test::set_account_balance::<ink::env::DefaultEnvironment>(contrato, saldo + monto);
+            test::set_value_transferred::<ink::env::DefaultEnvironment>(monto);
+        }
+
+        // --- Registro de usuarios ---
+        #[ink::test]
+        fn registrar_usuario_comprador_ok() {
+            let mut contrato = MarketplacePrincipal::new();
+
+            // Simulamos que el caller es "Alice"
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            // Llamamos a la función registrar_usuario con el rol de comprador
+            let resultado = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Verificamos que devuelva OK
+            assert_eq!(resultado, Ok(()));
+
+            // Obtenemos el usuario usando la dirección del caller
+            let usuario_registrado = contrato.usuarios.get(accounts.alice);
+
+            // Confirmamos si se guardó el usuario
+            assert!(usuario_registrado.is_some());
+
+            // Verificamos los datos
+            let usuario = usuario_registrado.unwrap();
+            assert_eq!(usuario.rol(), Some(RolUsuario::Comprador));
+            assert_eq!(usuario.reputacion_como_comprador, Reputacion::default());
+            assert_eq!(usuario.reputacion_como_vendedor, Reputacion::default());
+        }
+
+        #[ink::test]
+        fn registrar_usuario_vendedor_ok() {
+            let mut contrato = MarketplacePrincipal::new();
+
+            // Simulamos que el caller es "Bob"
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+
+            // Llamamos a la función registrar_usuario con el rol de vendedor
+            let resultado = contrato.registrar_usuario(RolUsuario::Vendedor);
+
+            // Verificamos que devuelva OK
+            assert_eq!(resultado, Ok(()));
+
+            // Obtenemos el usuario usando la dirección del caller
+            let usuario_registrado = contrato.usuarios.get(accounts.bob);
+
+            // Confirmamos si se guardó el usuario
+            assert!(usuario_registrado.is_some());
+
+            // Verificamos los datos
+            let usuario = usuario_registrado.unwrap();
+            assert_eq!(usuario.rol(), Some(RolUsuario::Vendedor));
+            assert_eq!(usuario.reputacion_como_comprador, Reputacion::default());
+            assert_eq!(usuario.reputacion_como_vendedor, Reputacion::default());
+        }
+
+        #[ink::test]
+        fn registrar_usuario_ambos_ok() {
+            let mut contrato = MarketplacePrincipal::new();
+
+            // Simulamos que el caller es "Charlie"
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+
+            // Llamamos a la función registrar_usuario con el rol de ambos
+            let resultado = contrato.registrar_usuario(RolUsuario::Ambos);
+
+            // Verificamos que devuelva OK
+            assert_eq!(resultado, Ok(()));
+
+            // Obtenemos el usuario usando la dirección del caller
+            let usuario_registrado = contrato.usuarios.get(accounts.charlie);
+
+            // Confirmamos si se guardó el usuario
+            assert!(usuario_registrado.is_some());
+
+            // Verificamos los datos
+            let usuario = usuario_registrado.unwrap();
+            assert_eq!(usuario.rol(), Some(RolUsuario::Ambos));
+            assert_eq!(usuario.reputacion_como_comprador, Reputacion::default());
+            assert_eq!(usuario.reputacion_como_vendedor, Reputacion::default());
+        }
+
+        #[ink::test]
+        fn registrar_usuario_duplicado_falla() {
+            let mut contrato = MarketplacePrincipal::new();
+
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            // Primer registro
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Segundo registro debería fallar porque ya está registrado
+            let resultado = contrato.registrar_usuario(RolUsuario::Vendedor);
+            assert_eq!(resultado, Err(SistemaError::UsuarioExistente));
+        }
+
+        // --- Modificación de roles ---
+        #[ink::test]
+        fn modificar_rol_usuario_comprador_a_vendedor_ok() {
+            // Contrato vacío: Alice usa la misma cuenta (0x01) que el vendedor del setup
+            let mut contrato = MarketplacePrincipal::new();
+
+            // Cambia el caller a un usuario registrado como Comprador
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Modifica el rol a Vendedor
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
+            assert!(resultado.is_ok());
+
+            // Verifica que el rol se haya actualizado correctamente
+            let usuario = contrato.obtener_usuario(accounts.alice).unwrap();
+            assert_eq!(usuario.rol(), Some(RolUsuario::Vendedor));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_vendedor_a_comprador_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Cambia el caller a un usuario registrado como Vendedor
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Vendedor);
+
+            // Modifica el rol a Comprador
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
+            assert!(resultado.is_ok());
+
+            // Verifica que el rol se haya actualizado correctamente
+            let usuario = contrato.obtener_usuario(accounts.bob).unwrap();
+            assert_eq!(usuario.rol(), Some(RolUsuario::Comprador));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_ambos_a_comprador_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Cambia el caller a un usuario registrado como Ambos
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let _ = contrato.registrar_usuario(RolUsuario::Ambos);
+
+            // Modifica el rol a Comprador
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
+            assert!(resultado.is_ok());
+
+            // Verifica que el rol se haya actualizado correctamente
+            let usuario = contrato.obtener_usuario(accounts.charlie).unwrap();
+            assert_eq!(usuario.rol(), Some(RolUsuario::Comprador));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_no_registrado_falla() {
+            let mut contrato = MarketplacePrincipal::new();
+
+            // Cambia el caller a un usuario no registrado
+            let caller = AccountId::from([0x05; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+
+            // Intenta modificar el rol
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
+            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_mismo_rol_falla() {
+            // Contrato vacío: Alice usa la misma cuenta (0x01) que el vendedor del setup
+            let mut contrato = MarketplacePrincipal::new();
+
+            // Cambia el caller a un usuario registrado como Comprador
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Intenta cambiar a Comprador nuevamente
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_no_puede_cambiar_a_vendedor_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Cambia el caller a un usuario registrado como Vendedor
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Vendedor);
+
+            // Intenta cambiar a Vendedor, lo cual no es permitido
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_no_puede_cambiar_a_comprador_falla() {
+            // Contrato vacío: Alice usa la misma cuenta (0x01) que el vendedor del setup
+            let mut contrato = MarketplacePrincipal::new();
+
+            // Cambia el caller a un usuario registrado como Comprador
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Intenta cambiar a Comprador, lo cual no es permitido
+            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_vendedor_a_ambos_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Vendedor);
+
+            assert!(contrato.modificar_rol_usuario(RolUsuario::Ambos).is_ok());
+            assert!(contrato.tiene_permiso(accounts.bob, Permiso::Comprar));
+            assert!(contrato.tiene_permiso(accounts.bob, Permiso::Vender));
+        }
+
+        #[ink::test]
+        fn modificar_rol_usuario_conserva_permisos_de_personal() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contrato = MarketplacePrincipal::con_arbitros(vec![accounts.bob]);
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+
+            // El árbitro queda registrado sin rol y puede elegir uno
+            assert_eq!(contrato.obtener_usuario(accounts.bob).unwrap().rol(), None);
+            assert!(contrato.modificar_rol_usuario(RolUsuario::Comprador).is_ok());
+            assert!(contrato.modificar_rol_usuario(RolUsuario::Vendedor).is_ok());
+
+            assert!(contrato.es_arbitro(accounts.bob));
+            assert!(!contrato.tiene_permiso(accounts.bob, Permiso::Comprar));
+        }
+
+        // --- Permisos ---
+        #[ink::test]
+        fn con_personal_registra_moderadores_y_arbitros() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contrato = MarketplacePrincipal::con_personal(
+                vec![accounts.charlie],
+                vec![accounts.charlie, accounts.django],
+            );
+
+            assert_eq!(contrato.cantidad_usuarios(), 2);
+            assert!(contrato.tiene_permiso(accounts.charlie, Permiso::Moderar));
+            assert!(contrato.tiene_permiso(accounts.charlie, Permiso::Arbitrar));
+            assert!(!contrato.tiene_permiso(accounts.django, Permiso::Moderar));
+            assert!(contrato.es_arbitro(accounts.django));
+        }
+
+        #[ink::test]
+        fn otorgar_y_revocar_permiso_ok() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contrato = MarketplacePrincipal::con_personal(vec![accounts.charlie], Vec::new());
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert!(contrato.otorgar_permiso(accounts.bob, Permiso::Arbitrar).is_ok());
+            assert!(contrato.es_arbitro(accounts.bob));
+            assert!(contrato.tiene_permiso(accounts.bob, Permiso::Comprar));
+
+            assert!(contrato.revocar_permiso(accounts.bob, Permiso::Comprar).is_ok());
+            assert!(!contrato.tiene_permiso(accounts.bob, Permiso::Comprar));
+            assert!(contrato.es_arbitro(accounts.bob));
+
+            let cantidad = test::recorded_events().count();
+            let evento = eventos_desde::<PermisoActualizado>(cantidad - 1).remove(0);
+            assert_eq!(evento.usuario, accounts.bob);
+            assert_eq!(evento.permiso, Permiso::Comprar);
+            assert!(!evento.otorgado);
+            assert_eq!(evento.moderador, accounts.charlie);
+        }
+
+        #[ink::test]
+        fn otorgar_permiso_sin_ser_moderador_falla() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contrato = MarketplacePrincipal::new();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.registrar_usuario(RolUsuario::Ambos).unwrap();
+
+            let resultado = contrato.otorgar_permiso(accounts.bob, Permiso::Moderar);
+            assert_eq!(resultado, Err(SistemaError::NoEsRolCorrecto));
+            assert!(!contrato.tiene_permiso(accounts.bob, Permiso::Moderar));
+        }
+
+        #[ink::test]
+        fn otorgar_permiso_a_no_registrado_falla() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contrato = MarketplacePrincipal::con_personal(vec![accounts.charlie], Vec::new());
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+
+            let resultado = contrato.otorgar_permiso(accounts.eve, Permiso::Comprar);
+            assert_eq!(resultado, Err(SistemaError::UsuarioNoRegistrado));
+        }
+
+        #[ink::test]
+        fn revocar_permiso_de_venta_bloquea_publicacion() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contrato = MarketplacePrincipal::con_personal(vec![accounts.charlie], Vec::new());
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.registrar_usuario(RolUsuario::Vendedor).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contrato.revocar_permiso(accounts.bob, Permiso::Vender).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let resultado = contrato.publicar_producto(
+                "Producto".to_string(),
+                "Sin permiso".to_string(),
+                100,
+                1,
+                "Otros".to_string(),
+            );
+            assert_eq!(resultado, Err(SistemaError::NoEsRolCorrecto));
+        }
+
+        #[ink::test]
+        fn cambiar_de_rol_no_recupera_un_permiso_revocado() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contrato = MarketplacePrincipal::con_personal(vec![accounts.charlie], Vec::new());
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.registrar_usuario(RolUsuario::Ambos).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contrato.revocar_permiso(accounts.bob, Permiso::Vender).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contrato.modificar_rol_usuario(RolUsuario::Vendedor), Err(SistemaError::PermisoBloqueado));
+            assert_eq!(contrato.modificar_rol_usuario(RolUsuario::Ambos), Err(SistemaError::PermisoBloqueado));
+            let resultado = contrato.publicar_producto(
+                "Producto".to_string(),
+                "Sin permiso".to_string(),
+                100,
+                1,
+                "Otros".to_string(),
+            );
+            assert_eq!(resultado, Err(SistemaError::NoEsRolCorrecto));
+
+            // Cuando un moderador lo vuelve a otorgar, el permiso deja de estar bloqueado
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contrato.otorgar_permiso(accounts.bob, Permiso::Vender).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(contrato.modificar_rol_usuario(RolUsuario::Vendedor).is_ok());
+            assert!(contrato.tiene_permiso(accounts.bob, Permiso::Vender));
+        }
+
+        // --- Publicación de productos ---
+        #[ink::test]
+        fn publicar_producto_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            let resultado = contrato.publicar_producto(
+                "Celular".to_string(),
+                "Un buen celular".to_string(),
+                1000,
+                5,
+                "Tecnología".to_string(),
+            );
+
+            assert!(resultado.is_ok());
+            assert_eq!(contrato.siguiente_id_producto, 1);
+
+            let producto = contrato.productos.get(0).unwrap();
+            assert_eq!(producto.nombre, "Celular");
+            assert_eq!(producto.precio, 1000);
+        }
+
+        #[ink::test]
+        fn publicar_productos_asigna_ids_consecutivos() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            for nombre in ["Celular", "Notebook", "Tablet"] {
+                contrato.publicar_producto(
+                    nombre.to_string(),
+                    "Descripción".to_string(),
+                    1000,
+                    5,
+                    "Tecnología".to_string(),
+                ).unwrap();
+            }
+
+            assert_eq!(contrato.siguiente_id_producto, 3);
+            for id in 0..3 {
+                assert_eq!(contrato.productos.get(id).unwrap().id, id);
+            }
+            assert_eq!(contrato.productos.get(2).unwrap().nombre, "Tablet");
+            assert!(contrato.productos.get(3).is_none());
+        }
+
+        #[ink::test]
+        fn publicar_producto_no_registrado_falla() {
+            let mut contrato = MarketplacePrincipal::new();
+
+            let caller = AccountId::from([0x02; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+
+            let resultado = contrato.publicar_producto(
+                "Producto".to_string(),
+                "Sin registro".to_string(),
+                500,
+                1,
+                "Otros".to_string(),
+            );
+
+            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+        }
+
+        #[ink::test]
+        fn publicar_producto_no_es_vendedor_falla() {
+            let mut contrato = MarketplacePrincipal::new();
+
+            let caller = AccountId::from([0x03; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+
+            // Rol no válido para publicar productos
+            let usuario = Usuario::new(caller, Permisos::de_rol(&RolUsuario::Comprador));
+            contrato.usuarios.insert(caller, &usuario);
+
+            let resultado = contrato.publicar_producto(
+                "Producto".to_string(),
+                "No autorizado".to_string(),
+                100,
+                2,
+                "Otros".to_string(),
+            );
+
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn publicar_producto_cantidad_cero_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            let resultado = contrato.publicar_producto(
+                "Producto".to_string(),
+                "Cantidad cero".to_string(),
+                100,
+                0, // Cantidad inválida
+                "Otros".to_string(),
+            );
+
+            assert!(matches!(resultado, Err(SistemaError::CantidadInsuficiente)));
+        }
+
+        // --- Compra y órdenes ---
+        #[ink::test]
+        fn crear_orden_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto y obtiene el ID
+            let _ = contrato.publicar_producto(
+                "Laptop".to_string(),
+                "Una laptop potente".to_string(),
+                2000,
+                10,
+                "Tecnología".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // El producto publicado tendrá id = 0 (si es el primero)
+            pagar(4000);
+            let resultado = contrato.crear_orden(0, 2);
+
+            assert!(resultado.is_ok());
+            let orden_id = resultado.unwrap();
+            assert_eq!(contrato.siguiente_id_orden, 1);
+
+            let orden = contrato.ordenes.get(0).unwrap();
+            assert_eq!(orden.id, orden_id);
+            assert_eq!(orden.lineas, vec![LineaOrden { producto_id: 0, cantidad: 2, monto: 4000 }]);
+            assert_eq!(orden.estado, EstadoOrden::Pendiente);
+        }
+
+        #[ink::test]
+        fn crear_orden_no_registrado_falla() {
+            let mut contrato = MarketplacePrincipal::new();
+
+            let caller = AccountId::from([0x04; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+
+            let resultado = contrato.crear_orden(0, 1);
+
+            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+        }
+
+        #[ink::test]
+        fn verificar_registro_antes_de_crear_orden() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+
+            // Cambia el caller a un usuario NO registrado
+            let nuevo_usuario = AccountId::from([0x99; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(nuevo_usuario);
+
+            // Verifica que el usuario no está registrado
+            assert!(!contrato.esta_registrado(nuevo_usuario));
+            assert!(contrato.obtener_usuario(nuevo_usuario).is_none());
+
+            // Intenta crear una orden y falla porque no está registrado
+            let resultado = contrato.crear_orden(0, 1);
+            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+
+            // Registra al usuario como comprador
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Verifica que ahora está registrado
+            assert!(contrato.esta_registrado(nuevo_usuario));
+            let usuario_info = contrato.obtener_usuario(nuevo_usuario).unwrap();
+            assert_eq!(usuario_info.rol(), Some(RolUsuario::Comprador));
+
+            // Ahora puede crear una orden exitosamente
+            pagar(1000);
+            let resultado = contrato.crear_orden(0, 1);
+            assert!(resultado.is_ok());
+        }
+
+        #[ink::test]
+        fn crear_orden_no_es_comprador_falla() {
+            let mut contrato = MarketplacePrincipal::new();
+
+            let caller = AccountId::from([0x05; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+
+            // Rol no válido para crear órdenes
+            let usuario = Usuario::new(caller, Permisos::de_rol(&RolUsuario::Vendedor));
+            contrato.usuarios.insert(caller, &usuario);
+
+            // Primero, publica un producto para poder comprarlo
+            let _ = contrato.publicar_producto(
+                "Tablet".to_string(),
+                "Una tablet versátil".to_string(),
+                1500,
+                7,
+                "Tecnología".to_string(),
+            );
+
+            let resultado = contrato.crear_orden(0, 1);
+
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn crear_orden_con_rol_ambos_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+
+            // Cambia el caller a un usuario con rol Ambos
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let _ = contrato.registrar_usuario(RolUsuario::Ambos);
+
+            // Debería poder crear una orden exitosamente
+            pagar(1000);
+            let resultado = contrato.crear_orden(0, 1);
+            assert!(resultado.is_ok());
+        }
+
+        #[ink::test]
+        fn crear_orden_cantidad_insuficiente_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Primero, publica un producto con cantidad insuficiente
+            let _ = contrato.publicar_producto(
+                "Smartwatch".to_string(),
+                "Un smartwatch elegante".to_string(),
+                500,
+                2, // Solo hay 2 disponibles
+                "Tecnología".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Intenta crear una orden de compra de 3 unidades
+            let resultado = contrato.crear_orden(0, 3); // Compra 3 unidades
+
+            assert!(matches!(resultado, Err(SistemaError::StockInsuficiente)));
+        }
+
+        #[ink::test]
+        fn crear_orden_cantidad_cero_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Intenta crear una orden con cantidad 0
+            let resultado = contrato.crear_orden(0, 0);
+
+            assert!(matches!(resultado, Err(SistemaError::CantidadInsuficiente)));
+        }
+
+        #[ink::test]
+        fn crear_orden_descuenta_stock() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Primero, publica un producto con cantidad suficiente
+            let _ = contrato.publicar_producto(
+                "Auriculares".to_string(),
+                "Auriculares inalámbricos".to_string(),
+                800,
+                10, // 10 disponibles
+                "Tecnología".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Crea una orden de compra
+            pagar(2400);
+            let resultado = contrato.crear_orden(0, 3); // Compra 3 unidades
+
+            assert!(resultado.is_ok());
+            let _orden_id = resultado.unwrap();
+            assert_eq!(contrato.siguiente_id_orden, 1);
+
+            // Verifica que el stock se haya descontado correctamente
+            let producto = contrato.productos.get(0).unwrap();
+            assert_eq!(producto.cantidad, 7); // Debería quedar 7 después de la compra
+        }
+
+        
+
+        // --- Gestión de órdenes ---
+        /* ESTOS QUE ESTAN COMENTADOS FALLAN
+        #[ink::test]
+        fn marcar_orden_como_enviada_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Primero, crea una orden
+            let orden_id = contrato.crear_orden(0, 1).unwrap();
+
+            // Marca la orden como enviada
+            let resultado = contrato.marcar_orden_como_enviada(orden_id);
+
+            assert!(resultado.is_ok());
+            let orden = contrato.ordenes.get(0).unwrap();
+            assert_eq!(orden.estado, EstadoOrden::Enviada);
+        }
+
+        #[ink::test]
+        fn marcar_orden_como_enviada_usuario_incorrecto_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Primero, crea una orden
+            let orden_id = contrato.crear_orden(0, 1).unwrap();
+
+            // Simula que otro usuario intenta marcar la orden como enviada
+            let otro_usuario = AccountId::from([0x06; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(otro_usuario);
+
+            let resultado = contrato.marcar_orden_como_enviada(orden_id);
+
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn marcar_como_recibida_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Primero, crea y envía una orden
+            let orden_id = contrato.crear_orden(0, 1).unwrap();
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+
+            let resultado = contrato.marcar_como_recibida(orden_id);
+
+            assert!(resultado.is_ok());
+            let orden = contrato.ordenes.get(0).unwrap();
+            assert_eq!(orden.estado, EstadoOrden::Recibida);
+        }
+
+        #[ink::test]
+        fn marcar_como_recibida_usuario_incorrecto_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Primero, crea y envía una orden
+            let orden_id = contrato.crear_orden(0, 1).unwrap();
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+
+            // Simula que otro usuario intenta marcar la orden como recibida
+            let otro_usuario = AccountId::from([0x07; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(otro_usuario);
+
+            let resultado = contrato.marcar_como_recibida(orden_id);
+
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn transicion_estado_invalida_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Primero, crea una orden
+            let orden_id = contrato.crear_orden(0, 1).unwrap();
+
+            // Simula que el vendedor intenta marcar la orden como recibida directamente
+            let resultado = contrato.marcar_como_recibida(orden_id);
+
+            assert!(matches!(resultado, Err(SistemaError::EstadoInvalido)));
+        }*/
+
+        // --- Errores y validaciones ---
+        #[ink::test]
+        fn acceder_orden_inexistente_falla() {
+            let contrato = MarketplacePrincipal::new();
+
+            let resultado = contrato.buscar_orden(999); // ID que no existe
+
+            assert!(matches!(resultado, Err(SistemaError::OrdenNoExiste)));
+        }
+
+        #[ink::test]
+        fn acceder_producto_inexistente_falla() {
+            let contrato = MarketplacePrincipal::new();
+
+            let resultado = contrato.buscar_producto(999); // ID que no existe
+
+            assert!(matches!(resultado, Err(SistemaError::ProductosVacios)));
+        }
+
+        #[ink::test]
+        fn marcar_orden_como_enviada_usuario_no_registrado_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Crea una orden
+            pagar(1000);
+            let resultado = contrato.crear_orden(0, 1);
+            assert!(resultado.is_ok());
+            let orden_id = resultado.unwrap();
+
+            // Cambia el caller a un usuario NO registrado
+            let usuario_no_registrado = AccountId::from([0x99; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(usuario_no_registrado);
+
+            // Intenta marcar la orden como enviada
+            let resultado = contrato.marcar_orden_como_enviada(orden_id);
+            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+        }
+
+        #[ink::test]
+        fn marcar_como_recibida_usuario_no_registrado_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Crea una orden
+            pagar(1000);
+            let resultado = contrato.crear_orden(0, 1);
+            assert!(resultado.is_ok());
+            let orden_id = resultado.unwrap();
+
+            // Cambia el caller a un usuario NO registrado
+            let usuario_no_registrado = AccountId::from([0x99; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(usuario_no_registrado);
+
+            // Intenta marcar la orden como recibida
+            let resultado = contrato.marcar_como_recibida(orden_id);
+            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+        }
+
+        #[ink::test]
+        fn marcar_orden_como_enviada_usuario_no_es_vendedor_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Crea una orden
+            pagar(1000);
+            let resultado = contrato.crear_orden(0, 1);
+            assert!(resultado.is_ok());
+            let orden_id = resultado.unwrap();
+
+            // Cambia el caller a otro usuario registrado que NO es el vendedor
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let _ = contrato.registrar_usuario(RolUsuario::Vendedor);
+
+            // Intenta marcar la orden como enviada (no debería poder porque no es el vendedor de esta orden)
+            let resultado = contrato.marcar_orden_como_enviada(orden_id);
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn marcar_como_recibida_usuario_no_es_comprador_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Crea una orden
+            pagar(1000);
+            let resultado = contrato.crear_orden(0, 1);
+            assert!(resultado.is_ok());
+            let orden_id = resultado.unwrap();
+
+            // Cambia el caller de vuelta al vendedor para marcar como enviada
+            let vendedor = AccountId::from([0x01; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+
+            // Marca la orden como enviada
+            let resultado = contrato.marcar_orden_como_enviada(orden_id);
+            assert!(resultado.is_ok());
+
+            // Cambia el caller a otro usuario registrado que NO es el comprador
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Intenta marcar la orden como recibida (no debería poder porque no es el comprador de esta orden)
+            let resultado = contrato.marcar_como_recibida(orden_id);
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn marcar_orden_como_enviada_orden_inexistente_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Intenta marcar una orden inexistente como enviada
+            let resultado = contrato.marcar_orden_como_enviada(999);
+            assert!(matches!(resultado, Err(SistemaError::OrdenNoExiste)));
+        }
+
+        #[ink::test]
+        fn marcar_como_recibida_orden_inexistente_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Intenta marcar una orden inexistente como recibida
+            let resultado = contrato.marcar_como_recibida(999);
+            assert!(matches!(resultado, Err(SistemaError::OrdenNoExiste)));
+        }
+
+        #[ink::test]
+        fn marcar_como_recibida_ok() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Crea una orden
+            pagar(1000);
+            let resultado = contrato.crear_orden(0, 1);
+            assert!(resultado.is_ok());
+            let orden_id = resultado.unwrap();
+
+            // Cambia el caller de vuelta al vendedor para marcar como enviada
+            let vendedor = AccountId::from([0x01; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+
+            // Marca la orden como enviada
+            let resultado = contrato.marcar_orden_como_enviada(orden_id);
+            assert!(resultado.is_ok());
+
+            // Cambia el caller de vuelta al comprador para marcar como recibida
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+
+            // Marca la orden como recibida (debe ser exitoso)
+            let resultado = contrato.marcar_como_recibida(orden_id);
+            assert!(resultado.is_ok());
+
+            // Verifica que el estado cambió a Recibida
+            let orden = contrato.ordenes.get(orden_id).unwrap();
+            assert_eq!(orden.estado, EstadoOrden::Recibida);
+        }
+
+        #[ink::test]
+        fn marcar_como_recibida_estado_pendiente_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+
+            // Publica un producto
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+
+            // Cambia el caller a un usuario comprador y regístralo
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Crea una orden (estado inicial: Pendiente)
+            pagar(1000);
+            let resultado = contrato.crear_orden(0, 1);
+            assert!(resultado.is_ok());
+            let orden_id = resultado.unwrap();
+
+            // Verifica que la orden está en estado Pendiente
+            let orden = contrato.ordenes.get(orden_id).unwrap();
+            assert_eq!(orden.estado, EstadoOrden::Pendiente);
+
+            // Intenta marcar la orden como recibida directamente desde Pendiente (debe fallar)
+            let resultado = contrato.marcar_como_recibida(orden_id);
+            assert!(matches!(resultado, Err(SistemaError::EstadoInvalido)));
+        }
+
+        // --- Cancelación por consentimiento mutuo ---
+
+        // Función auxiliar que deja una orden pendiente de 2 unidades (vendedor 0x01, comprador Bob)
+        fn setup_orden_pendiente() -> (MarketplacePrincipal, u32) {
+            let mut contrato = setup_contract_con_vendedor();
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            pagar(2000);
+            let orden_id = contrato.crear_orden(0, 2).unwrap();
+            (contrato, orden_id)
+        }
+
+        #[ink::test]
+        fn cancelacion_mutua_ok() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 8);
+
+            // El comprador (caller actual) solicita la cancelación
+            let resultado = contrato.solicitar_cancelacion(orden_id);
+            assert!(resultado.is_ok());
+            assert_eq!(contrato.ordenes.get(orden_id).unwrap().estado, EstadoOrden::Pendiente);
+
+            // El vendedor la acepta
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            let resultado = contrato.aceptar_cancelacion(orden_id);
+            assert!(resultado.is_ok());
+
+            // La orden queda cancelada y el stock vuelve al producto
+            assert_eq!(contrato.ordenes.get(orden_id).unwrap().estado, EstadoOrden::Cancelada);
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 10);
+        }
+
+        #[ink::test]
+        fn aceptar_cancelacion_sin_solicitud_falla() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+
+            // El vendedor intenta aceptar una cancelación que nadie pidió
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            let resultado = contrato.aceptar_cancelacion(orden_id);
+            assert!(matches!(resultado, Err(SistemaError::CancelacionNoSolicitada)));
+        }
+
+        #[ink::test]
+        fn aceptar_cancelacion_propia_falla() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+
+            // El comprador solicita y luego intenta aceptar su propia solicitud
+            let _ = contrato.solicitar_cancelacion(orden_id);
+            let resultado = contrato.aceptar_cancelacion(orden_id);
+            assert!(matches!(resultado, Err(SistemaError::CancelacionNoSolicitada)));
+            assert_eq!(contrato.ordenes.get(orden_id).unwrap().estado, EstadoOrden::Pendiente);
+        }
+
+        #[ink::test]
+        fn solicitar_cancelacion_duplicada_falla() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+
+            let _ = contrato.solicitar_cancelacion(orden_id);
+
+            // El vendedor no puede abrir una segunda solicitud, debe aceptar la existente
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            let resultado = contrato.solicitar_cancelacion(orden_id);
+            assert!(matches!(resultado, Err(SistemaError::CancelacionYaSolicitada)));
+        }
+
+        #[ink::test]
+        fn solicitar_cancelacion_usuario_ajeno_falla() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+
+            // Un usuario registrado que no participa de la orden
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            let resultado = contrato.solicitar_cancelacion(orden_id);
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn solicitar_cancelacion_orden_enviada_falla() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+
+            // El vendedor envía la orden
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            let _ = contrato.marcar_orden_como_enviada(orden_id);
+
+            // Ya no se puede cancelar
+            let resultado = contrato.solicitar_cancelacion(orden_id);
+            assert!(matches!(resultado, Err(SistemaError::EstadoInvalido)));
+        }
+
+        #[ink::test]
+        fn cancelacion_orden_inexistente_falla() {
+            let (mut contrato, _) = setup_orden_pendiente();
+
+            assert!(matches!(contrato.solicitar_cancelacion(999), Err(SistemaError::OrdenNoExiste)));
+            assert!(matches!(contrato.aceptar_cancelacion(999), Err(SistemaError::OrdenNoExiste)));
+        }
+
+        // --- Calificaciones ---
+
+        // Función auxiliar que deja una orden recibida (vendedor 0x01, comprador Bob)
+        fn setup_orden_recibida() -> (MarketplacePrincipal, u32) {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.marcar_como_recibida(orden_id).unwrap();
+            (contrato, orden_id)
+        }
+
+        #[ink::test]
+        fn orden_recibida_suma_unidades_vendidas() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
+            // Mientras la orden no se recibe, las unidades no cuentan como vendidas
+            assert_eq!(contrato.productos.get(0).unwrap().unidades_vendidas, 0);
+
+            // Una orden cancelada tampoco suma ventas
+            pagar(1000);
+            let cancelada = contrato.crear_orden(0, 1).unwrap();
+            contrato.solicitar_cancelacion(cancelada).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.aceptar_cancelacion(cancelada).unwrap();
+            assert_eq!(contrato.productos.get(0).unwrap().unidades_vendidas, 0);
+
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.marcar_como_recibida(orden_id).unwrap();
+            assert_eq!(contrato.productos.get(0).unwrap().unidades_vendidas, 2);
+        }
+
+        #[ink::test]
+        fn calificar_vendedor_ok() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            let vendedor = AccountId::from([0x01; 32]);
+
+            // El comprador (Bob) califica al vendedor
+            let resultado = contrato.calificar_vendedor(orden_id, 4);
+            assert!(resultado.is_ok());
+
+            let usuario = contrato.obtener_usuario(vendedor).unwrap();
+            assert_eq!(usuario.reputacion_como_vendedor.total_puntos, 4);
+            assert_eq!(usuario.reputacion_como_vendedor.cantidad_calificaciones, 1);
+            assert_eq!(usuario.reputacion_como_vendedor.histograma, [0, 0, 0, 1, 0]);
+            assert!(contrato.ordenes.get(orden_id).unwrap().comprador_califico);
+            assert_eq!(contrato.ordenes.get(orden_id).unwrap().puntaje_al_vendedor, Some(4));
+        }
+
+        #[ink::test]
+        fn calificar_comprador_ok() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            // El vendedor califica al comprador (Bob)
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            let resultado = contrato.calificar_comprador(orden_id, 5);
+            assert!(resultado.is_ok());
+
+            let usuario = contrato.obtener_usuario(accounts.bob).unwrap();
+            assert_eq!(usuario.reputacion_como_comprador.total_puntos, 5);
+            assert_eq!(usuario.reputacion_como_comprador.histograma, [0, 0, 0, 0, 1]);
+            assert!(contrato.ordenes.get(orden_id).unwrap().vendedor_califico);
+        }
+
+        #[ink::test]
+        fn calificar_puntaje_fuera_de_rango_falla() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+
+            assert!(matches!(contrato.calificar_vendedor(orden_id, 0), Err(SistemaError::PuntajeInvalido)));
+            assert!(matches!(contrato.calificar_vendedor(orden_id, 6), Err(SistemaError::PuntajeInvalido)));
+        }
+
+        #[ink::test]
+        fn calificar_orden_no_recibida_falla() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+
+            // La orden sigue pendiente
+            let resultado = contrato.calificar_vendedor(orden_id, 3);
+            assert!(matches!(resultado, Err(SistemaError::OrdenNoRecibida)));
+        }
+
+        #[ink::test]
+        fn calificar_dos_veces_falla() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+
+            let _ = contrato.calificar_vendedor(orden_id, 5);
+            let resultado = contrato.calificar_vendedor(orden_id, 1);
+            assert!(matches!(resultado, Err(SistemaError::YaCalificado)));
+
+            // La reputación no se modifica con el segundo intento
+            let usuario = contrato.obtener_usuario(AccountId::from([0x01; 32])).unwrap();
+            assert_eq!(usuario.reputacion_como_vendedor.total_puntos, 5);
+            assert_eq!(usuario.reputacion_como_vendedor.cantidad_calificaciones, 1);
+        }
+
+        #[ink::test]
+        fn calificar_parte_incorrecta_falla() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+
+            // El comprador no puede calificarse a sí mismo como comprador
+            let resultado = contrato.calificar_comprador(orden_id, 5);
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+
+            // El vendedor no puede calificarse como vendedor
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            let resultado = contrato.calificar_vendedor(orden_id, 5);
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        // --- Reputación ---
+        #[ink::test]
+        fn reputacion_promedio_en_punto_fijo() {
+            let mut reputacion = Reputacion::default();
+            assert_eq!(reputacion.promedio(), 0);
+
+            reputacion.registrar(5);
+            reputacion.registrar(4);
+            reputacion.registrar(4);
+
+            // (5 + 4 + 4) / 3 = 4,33 -> 433
+            assert_eq!(reputacion.promedio(), 433);
+            assert_eq!(reputacion.cantidad_calificaciones, 3);
+            assert_eq!(reputacion.histograma, [0, 0, 0, 2, 1]);
+        }
+
+        #[ink::test]
+        fn obtener_reputacion_ok() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
+
+            let _ = contrato.calificar_vendedor(orden_id, 3);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            let _ = contrato.calificar_comprador(orden_id, 5);
+
+            let resumen_vendedor = contrato.obtener_reputacion(vendedor).unwrap();
+            assert_eq!(resumen_vendedor.promedio_como_vendedor, 300);
+            assert_eq!(resumen_vendedor.calificaciones_como_vendedor, 1);
+            assert_eq!(resumen_vendedor.calificaciones_como_comprador, 0);
+
+            let resumen_comprador = contrato.obtener_reputacion(accounts.bob).unwrap();
+            assert_eq!(resumen_comprador.promedio_como_comprador, 500);
+            assert_eq!(resumen_comprador.calificaciones_como_comprador, 1);
+        }
+
+        #[ink::test]
+        fn obtener_reputacion_no_registrado() {
+            let contrato = MarketplacePrincipal::new();
+            assert!(contrato.obtener_reputacion(AccountId::from([0x42; 32])).is_none());
+        }
+
+        // --- Pagos retenidos ---
+        fn balance_de(cuenta: AccountId) -> Balance {
+            test::get_account_balance::<ink::env::DefaultEnvironment>(cuenta).unwrap_or(0)
+        }
+
+        #[ink::test]
+        fn crear_orden_pago_incorrecto_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+            let _ = contrato.publicar_producto(
+                "Producto Test".to_string(),
+                "Descripción Test".to_string(),
+                1000,
+                10,
+                "Test".to_string(),
+            );
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+
+            // Sin pago
+            test::set_value_transferred::<ink::env::DefaultEnvironment>(0);
+            assert!(matches!(contrato.crear_orden(0, 2), Err(SistemaError::PagoIncorrecto)));
+
+            // Pago de menos y de más
+            pagar(1999);
+            assert!(matches!(contrato.crear_orden(0, 2), Err(SistemaError::PagoIncorrecto)));
+            pagar(2001);
+            assert!(matches!(contrato.crear_orden(0, 2), Err(SistemaError::PagoIncorrecto)));
+
+            // No se descontó stock ni se creó la orden
+            assert_eq!(contrato.productos.get(0).unwrap().cantidad, 10);
+            assert_eq!(contrato.siguiente_id_orden, 0);
+        }
+
+        #[ink::test]
+        fn crear_orden_registra_monto_retenido() {
+            let (contrato, orden_id) = setup_orden_pendiente();
+            assert_eq!(contrato.ordenes.get(orden_id).unwrap().monto, 2000);
+        }
+
+        #[ink::test]
+        fn marcar_como_recibida_acredita_pago_al_vendedor() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let vendedor = AccountId::from([0x01; 32]);
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let cuenta_contrato = test::callee::<ink::env::DefaultEnvironment>();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+
+            let saldo_vendedor = balance_de(vendedor);
+            let saldo_contrato = balance_de(cuenta_contrato);
+
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.marcar_como_recibida(orden_id).unwrap();
+
+            // El pago queda en el contrato, acreditado al vendedor hasta que lo retire
+            assert_eq!(contrato.saldo_de(vendedor), 2000);
+            assert_eq!(balance_de(vendedor), saldo_vendedor);
+            assert_eq!(balance_de(cuenta_contrato), saldo_contrato);
+        }
+
+        #[ink::test]
+        fn cancelacion_reembolsa_al_comprador() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let saldo_comprador = balance_de(accounts.bob);
+
+            let _ = contrato.solicitar_cancelacion(orden_id);
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            contrato.aceptar_cancelacion(orden_id).unwrap();
+
+            // El reembolso queda acreditado hasta que el comprador lo retire
+            assert_eq!(contrato.saldo_de(accounts.bob), 2000);
+            assert_eq!(balance_de(accounts.bob), saldo_comprador);
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.retirar_fondos(2000).unwrap();
+            assert_eq!(balance_de(accounts.bob), saldo_comprador + 2000);
+        }
+
+        // --- Disputas ---
+
+        // Función auxiliar que otorga el permiso de arbitraje, registrando la cuenta si hace falta
+        fn habilitar_arbitro(contrato: &mut MarketplacePrincipal, cuenta: AccountId) {
+            let mut usuario = contrato.usuarios.get(cuenta)
+                .unwrap_or_else(|| Usuario::new(cuenta, Permisos::default()));
+            usuario.permisos.otorgar(Permiso::Arbitrar);
+            contrato.usuarios.insert(cuenta, &usuario);
+        }
+
+        // Función auxiliar que deja una orden enviada con Django como árbitro
+        fn setup_orden_enviada() -> (MarketplacePrincipal, u32) {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            habilitar_arbitro(&mut contrato, accounts.django);
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            (contrato, orden_id)
+        }
+
+        #[ink::test]
+        fn constructor_con_arbitros_ok() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contrato = MarketplacePrincipal::con_arbitros(vec![accounts.django]);
+            assert!(contrato.es_arbitro(accounts.django));
+            assert!(!contrato.es_arbitro(accounts.eve));
+        }
+
+        #[ink::test]
+        fn designar_arbitro_resuelve_disputas_sin_arbitros_iniciales() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let propietario = AccountId::from([0x01; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(propietario);
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.abrir_disputa(orden_id, Hash::from([0x07; 32])).unwrap();
+
+            // El contrato creado con `new` no tiene quién resuelva la disputa
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(
+                contrato.resolver_disputa(orden_id, ResolucionDisputa::ReembolsarComprador),
+                Err(SistemaError::NoEsArbitro)
+            );
+            assert_eq!(contrato.designar_arbitro(accounts.django), Err(SistemaError::NoEsAdministrador));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(propietario);
+            contrato.designar_arbitro(accounts.django).unwrap();
+            assert!(contrato.es_arbitro(accounts.django));
+            // El árbitro queda registrado sin permisos de compra ni venta
+            assert!(!contrato.tiene_permiso(accounts.django, Permiso::Comprar));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            contrato.resolver_disputa(orden_id, ResolucionDisputa::ReembolsarComprador).unwrap();
+            assert_eq!(contrato.saldo_de(accounts.bob), 2000);
+        }
+
+        #[ink::test]
+        fn abrir_disputa_ok() {
+            let (mut contrato, orden_id) = setup_orden_enviada();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            let resultado = contrato.abrir_disputa(orden_id, Hash::from([0x07; 32]));
+            assert!(resultado.is_ok());
+
+            let orden = contrato.ordenes.get(orden_id).unwrap();
+            assert_eq!(orden.estado, EstadoOrden::EnDisputa);
+            let disputa = orden.disputa.clone().unwrap();
+            assert_eq!(disputa.iniciador, accounts.bob);
+            assert_eq!(disputa.motivo_hash, Hash::from([0x07; 32]));
+            assert_eq!(disputa.resolucion, None);
+        }
+
+        #[ink::test]
+        fn abrir_disputa_orden_pendiente_falla() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let resultado = contrato.abrir_disputa(orden_id, Hash::from([0x07; 32]));
+            assert!(matches!(resultado, Err(SistemaError::EstadoInvalido)));
+        }
+
         #[ink::test]
-        fn registrar_usuario_comprador_ok() {
-            let mut contrato = MarketplacePrincipal::new();
+        fn abrir_disputa_usuario_ajeno_falla() {
+            let (mut contrato, orden_id) = setup_orden_enviada();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            // Simulamos que el caller es "Alice"
+            let resultado = contrato.abrir_disputa(orden_id, Hash::from([0x07; 32]));
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
+
+        #[ink::test]
+        fn resolver_disputa_reembolso_ok() {
+            let (mut contrato, orden_id) = setup_orden_enviada();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let _ = contrato.abrir_disputa(orden_id, Hash::from([0x07; 32]));
+            let saldo_comprador = balance_de(accounts.bob);
 
-            // Llamamos a la función registrar_usuario con el rol de comprador
-            let resultado = contrato.registrar_usuario(RolUsuario::Comprador);
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            let resultado = contrato.resolver_disputa(orden_id, ResolucionDisputa::ReembolsarComprador);
+            assert!(resultado.is_ok());
 
-            // Verificamos que devuelva OK
-            assert_eq!(resultado, Ok(()));
+            let orden = contrato.ordenes.get(orden_id).unwrap();
+            assert_eq!(orden.estado, EstadoOrden::Cancelada);
+            assert_eq!(
+                orden.disputa.clone().unwrap().resolucion,
+                Some(ResolucionDisputa::ReembolsarComprador)
+            );
+            assert_eq!(contrato.saldo_de(accounts.bob), 2000);
+            assert_eq!(balance_de(accounts.bob), saldo_comprador);
+            assert_eq!(contrato.productos.get(0).unwrap().unidades_vendidas, 0);
+        }
 
-            // Obtenemos el usuario usando la dirección del caller
-            let usuario_registrado = contrato.usuarios.get(&accounts.alice);
+        #[ink::test]
+        fn resolver_disputa_pagar_vendedor_ok() {
+            let (mut contrato, orden_id) = setup_orden_enviada();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
+            let _ = contrato.abrir_disputa(orden_id, Hash::from([0x07; 32]));
 
-            // Confirmamos si se guardó el usuario
-            assert!(usuario_registrado.is_some());
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            let resultado = contrato.resolver_disputa(orden_id, ResolucionDisputa::PagarVendedor);
+            assert!(resultado.is_ok());
 
-            // Verificamos los datos
-            let usuario = usuario_registrado.unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Comprador);
-            assert_eq!(usuario.reputacion_como_comprador, 0);
-            assert_eq!(usuario.reputacion_como_vendedor, 0);
+            assert_eq!(contrato.ordenes.get(orden_id).unwrap().estado, EstadoOrden::Recibida);
+            assert_eq!(contrato.saldo_de(vendedor), 2000);
+            // La orden resuelta a favor del vendedor cuenta como venta concretada
+            assert_eq!(contrato.productos.get(0).unwrap().unidades_vendidas, 2);
         }
 
         #[ink::test]
-        fn registrar_usuario_vendedor_ok() {
-            let mut contrato = MarketplacePrincipal::new();
-
-            // Simulamos que el caller es "Bob"
+        fn resolver_disputa_dividir_ok() {
+            let (mut contrato, orden_id) = setup_orden_enviada();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-
-            // Llamamos a la función registrar_usuario con el rol de vendedor
-            let resultado = contrato.registrar_usuario(RolUsuario::Vendedor);
+            let vendedor = AccountId::from([0x01; 32]);
+            let _ = contrato.abrir_disputa(orden_id, Hash::from([0x07; 32]));
+            let saldo_comprador = balance_de(accounts.bob);
 
-            // Verificamos que devuelva OK
-            assert_eq!(resultado, Ok(()));
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            let resultado = contrato.resolver_disputa(orden_id, ResolucionDisputa::Dividir { monto_comprador: 500 });
+            assert!(resultado.is_ok());
 
-            // Obtenemos el usuario usando la dirección del caller
-            let usuario_registrado = contrato.usuarios.get(&accounts.bob);
+            assert_eq!(contrato.saldo_de(accounts.bob), 500);
+            assert_eq!(balance_de(accounts.bob), saldo_comprador);
+            assert_eq!(contrato.saldo_de(vendedor), 1500);
+        }
 
-            // Confirmamos si se guardó el usuario
-            assert!(usuario_registrado.is_some());
+        #[ink::test]
+        fn resolver_disputa_dividir_monto_excedido_falla() {
+            let (mut contrato, orden_id) = setup_orden_enviada();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let _ = contrato.abrir_disputa(orden_id, Hash::from([0x07; 32]));
 
-            // Verificamos los datos
-            let usuario = usuario_registrado.unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Vendedor);
-            assert_eq!(usuario.reputacion_como_comprador, 0);
-            assert_eq!(usuario.reputacion_como_vendedor, 0);
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            let resultado = contrato.resolver_disputa(orden_id, ResolucionDisputa::Dividir { monto_comprador: 2001 });
+            assert!(matches!(resultado, Err(SistemaError::MontoInvalido)));
+            assert_eq!(contrato.ordenes.get(orden_id).unwrap().estado, EstadoOrden::EnDisputa);
         }
 
         #[ink::test]
-        fn registrar_usuario_ambos_ok() {
-            let mut contrato = MarketplacePrincipal::new();
+        fn resolver_disputa_no_arbitro_falla() {
+            let (mut contrato, orden_id) = setup_orden_enviada();
+            let _ = contrato.abrir_disputa(orden_id, Hash::from([0x07; 32]));
 
-            // Simulamos que el caller es "Charlie"
+            // El propio comprador no puede resolver la disputa
+            let resultado = contrato.resolver_disputa(orden_id, ResolucionDisputa::ReembolsarComprador);
+            assert!(matches!(resultado, Err(SistemaError::NoEsArbitro)));
+        }
+
+        #[ink::test]
+        fn resolver_disputa_arbitro_parte_de_la_orden_falla() {
+            let (mut contrato, orden_id) = setup_orden_enviada();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let _ = contrato.abrir_disputa(orden_id, Hash::from([0x07; 32]));
+            habilitar_arbitro(&mut contrato, accounts.bob);
 
-            // Llamamos a la función registrar_usuario con el rol de ambos
-            let resultado = contrato.registrar_usuario(RolUsuario::Ambos);
+            let resultado = contrato.resolver_disputa(orden_id, ResolucionDisputa::ReembolsarComprador);
+            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        }
 
-            // Verificamos que devuelva OK
-            assert_eq!(resultado, Ok(()));
+        #[ink::test]
+        fn resolver_disputa_sin_disputa_abierta_falla() {
+            let (mut contrato, orden_id) = setup_orden_enviada();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
 
-            // Obtenemos el usuario usando la dirección del caller
-            let usuario_registrado = contrato.usuarios.get(&accounts.charlie);
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            let resultado = contrato.resolver_disputa(orden_id, ResolucionDisputa::PagarVendedor);
+            assert!(matches!(resultado, Err(SistemaError::EstadoInvalido)));
+        }
 
-            // Confirmamos si se guardó el usuario
-            assert!(usuario_registrado.is_some());
+        // --- Eventos ---
 
-            // Verificamos los datos
-            let usuario = usuario_registrado.unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Ambos);
-            assert_eq!(usuario.reputacion_como_comprador, 0);
-            assert_eq!(usuario.reputacion_como_vendedor, 0);
+        // Decodifica como `T` todos los eventos emitidos desde la posición `desde`
+        fn eventos_desde<T: Decode>(desde: usize) -> Vec<T> {
+            test::recorded_events()
+                .skip(desde)
+                .map(|evento| T::decode(&mut &evento.data[..]).unwrap())
+                .collect()
         }
 
         #[ink::test]
-        fn registrar_usuario_duplicado_falla() {
+        fn registrar_usuario_emite_evento() {
             let mut contrato = MarketplacePrincipal::new();
-
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
 
-            // Primer registro
             let _ = contrato.registrar_usuario(RolUsuario::Comprador);
-
-            // Segundo registro debería fallar porque ya está registrado
-            let resultado = contrato.registrar_usuario(RolUsuario::Vendedor);
-            assert_eq!(resultado, Err(SistemaError::UsuarioExistente));
+            let _ = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
+
+            let eventos = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(eventos.len(), 2);
+            let registrado = UsuarioRegistrado::decode(&mut &eventos[0].data[..]).unwrap();
+            assert_eq!(registrado.usuario, accounts.bob);
+            assert_eq!(registrado.rol, RolUsuario::Comprador);
+            let modificado = RolModificado::decode(&mut &eventos[1].data[..]).unwrap();
+            assert_eq!(modificado.rol_anterior, Some(RolUsuario::Comprador));
+            assert_eq!(modificado.rol_nuevo, RolUsuario::Vendedor);
         }
 
-        // --- Modificación de roles ---
         #[ink::test]
-        fn modificar_rol_usuario_comprador_a_vendedor_ok() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn publicar_y_comprar_emite_eventos() {
+            let (_, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
 
-            // Cambia el caller a un usuario registrado como Comprador
+            // Registro del vendedor, publicación, registro del comprador, stock y orden
+            let eventos = test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(eventos.len(), 5);
+            let publicado = ProductoPublicado::decode(&mut &eventos[1].data[..]).unwrap();
+            assert_eq!(publicado.producto_id, 0);
+            assert_eq!(publicado.cantidad, 10);
+            let stock = StockActualizado::decode(&mut &eventos[3].data[..]).unwrap();
+            assert_eq!(stock.stock, 8);
+            let creada = OrdenCreada::decode(&mut &eventos[4].data[..]).unwrap();
+            assert_eq!(creada.orden_id, orden_id);
+            assert_eq!(creada.comprador, accounts.bob);
+            assert_eq!(creada.monto, 2000);
+        }
+
+        #[ink::test]
+        fn transiciones_de_orden_emiten_eventos() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            let desde = test::recorded_events().count();
 
-            // Modifica el rol a Vendedor
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
-            assert!(resultado.is_ok());
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.marcar_como_recibida(orden_id).unwrap();
+
+            let eventos = eventos_desde::<EstadoOrdenActualizado>(desde);
+            assert_eq!(eventos.len(), 2);
+            assert_eq!(eventos[0].estado_anterior, EstadoOrden::Pendiente);
+            assert_eq!(eventos[0].estado_nuevo, EstadoOrden::Enviada);
+            assert_eq!(eventos[1].estado_anterior, EstadoOrden::Enviada);
+            assert_eq!(eventos[1].estado_nuevo, EstadoOrden::Recibida);
+
+            let desde = test::recorded_events().count();
+            contrato.calificar_vendedor(orden_id, 4).unwrap();
+            let calificaciones = eventos_desde::<CalificacionRegistrada>(desde);
+            assert_eq!(calificaciones[0].calificado, AccountId::from([0x01; 32]));
+            assert_eq!(calificaciones[0].puntaje, 4);
+        }
 
-            // Verifica que el rol se haya actualizado correctamente
-            let usuario = contrato.obtener_usuario(accounts.alice).unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Vendedor);
+        #[ink::test]
+        fn cancelacion_emite_eventos() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let desde = test::recorded_events().count();
+
+            contrato.solicitar_cancelacion(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            contrato.aceptar_cancelacion(orden_id).unwrap();
+
+            // Solicitud, transición a Cancelada y devolución de stock
+            let eventos = test::recorded_events().skip(desde).collect::<Vec<_>>();
+            assert_eq!(eventos.len(), 3);
+            let solicitud = CancelacionSolicitada::decode(&mut &eventos[0].data[..]).unwrap();
+            assert_eq!(solicitud.solicitante, accounts.bob);
+            let transicion = EstadoOrdenActualizado::decode(&mut &eventos[1].data[..]).unwrap();
+            assert_eq!(transicion.estado_nuevo, EstadoOrden::Cancelada);
+            let stock = StockActualizado::decode(&mut &eventos[2].data[..]).unwrap();
+            assert_eq!(stock.stock, 10);
         }
 
+        // --- Consultas paginadas ---
         #[ink::test]
-        fn modificar_rol_usuario_vendedor_a_comprador_ok() {
+        fn listar_productos_pagina_con_cursor() {
             let mut contrato = setup_contract_con_vendedor();
+            for i in 0..5u32 {
+                contrato.publicar_producto(format!("Producto {}", i), "Desc".to_string(), 100, 1, "Otros".to_string()).unwrap();
+            }
 
-            // Cambia el caller a un usuario registrado como Vendedor
-            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Vendedor);
+            let pagina = contrato.listar_productos(0, 2);
+            assert_eq!(pagina.elementos.iter().map(|p| p.id).collect::<Vec<_>>(), vec![0, 1]);
+            assert_eq!(pagina.siguiente, Some(2));
 
-            // Modifica el rol a Comprador
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
-            assert!(resultado.is_ok());
+            let pagina = contrato.listar_productos(2, 2);
+            assert_eq!(pagina.elementos.iter().map(|p| p.id).collect::<Vec<_>>(), vec![2, 3]);
+            assert_eq!(pagina.siguiente, Some(4));
 
-            // Verifica que el rol se haya actualizado correctamente
-            let usuario = contrato.obtener_usuario(accounts.bob).unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Comprador);
+            let pagina = contrato.listar_productos(4, 2);
+            assert_eq!(pagina.elementos.len(), 1);
+            assert_eq!(pagina.siguiente, None);
+
+            // Un cursor fuera de rango devuelve una página vacía
+            let pagina = contrato.listar_productos(10, 2);
+            assert!(pagina.elementos.is_empty());
+            assert_eq!(pagina.siguiente, None);
         }
 
         #[ink::test]
-        fn modificar_rol_usuario_ambos_a_comprador_ok() {
+        fn listar_productos_acota_el_limite() {
             let mut contrato = setup_contract_con_vendedor();
+            for _ in 0..(LIMITE_PAGINA + 1) {
+                contrato.publicar_producto("Producto".to_string(), "Desc".to_string(), 100, 1, "Otros".to_string()).unwrap();
+            }
 
-            // Cambia el caller a un usuario registrado como Ambos
+            let pagina = contrato.listar_productos(0, u32::MAX);
+            assert_eq!(pagina.elementos.len(), LIMITE_PAGINA as usize);
+            assert_eq!(pagina.siguiente, Some(LIMITE_PAGINA));
+        }
+
+        #[ink::test]
+        fn listar_ordenes_y_obtener_por_id() {
+            let (contrato, orden_id) = setup_orden_pendiente();
+
+            let pagina = contrato.listar_ordenes(0, 10);
+            assert_eq!(pagina.elementos.len(), 1);
+            assert_eq!(pagina.elementos[0].id, orden_id);
+            assert_eq!(pagina.siguiente, None);
+
+            assert_eq!(contrato.obtener_orden(orden_id).unwrap().lineas[0].cantidad, 2);
+            assert!(contrato.obtener_orden(orden_id + 1).is_none());
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 8);
+            assert!(contrato.obtener_producto(1).is_none());
+        }
+
+        #[ink::test]
+        fn listar_usuarios_en_orden_de_registro() {
+            let mut contrato = setup_contract_con_vendedor();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
             test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
-            let _ = contrato.registrar_usuario(RolUsuario::Ambos);
-
-            // Modifica el rol a Comprador
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
-            assert!(resultado.is_ok());
+            contrato.registrar_usuario(RolUsuario::Ambos).unwrap();
+
+            assert_eq!(contrato.cantidad_usuarios(), 3);
+            let pagina = contrato.listar_usuarios(0, 2);
+            assert_eq!(pagina.elementos[0].direccion, AccountId::from([0x01; 32]));
+            assert_eq!(pagina.elementos[1].direccion, accounts.bob);
+            assert_eq!(pagina.siguiente, Some(2));
+            let pagina = contrato.listar_usuarios(2, 10);
+            assert_eq!(pagina.elementos[0].direccion, accounts.charlie);
+            assert_eq!(pagina.elementos[0].rol(), Some(RolUsuario::Ambos));
+            assert_eq!(pagina.siguiente, None);
+
+            // Un registro repetido no duplica la cuenta en el listado
+            assert!(contrato.registrar_usuario(RolUsuario::Comprador).is_err());
+            assert_eq!(contrato.cantidad_usuarios(), 3);
+        }
 
-            // Verifica que el rol se haya actualizado correctamente
-            let usuario = contrato.obtener_usuario(accounts.charlie).unwrap();
-            assert_eq!(usuario.rol, RolUsuario::Comprador);
+        // --- Ciclo de vida de productos ---
+        // Contrato con un producto publicado por el vendedor del setup (precio 1000, stock 10)
+        fn setup_producto_publicado() -> MarketplacePrincipal {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.publicar_producto(
+                "Celular".to_string(),
+                "Un buen celular".to_string(),
+                1000,
+                10,
+                "Tecnología".to_string(),
+            ).unwrap();
+            contrato
         }
 
         #[ink::test]
-        fn modificar_rol_usuario_no_registrado_falla() {
-            let mut contrato = MarketplacePrincipal::new();
+        fn editar_producto_ok() {
+            let mut contrato = setup_producto_publicado();
 
-            // Cambia el caller a un usuario no registrado
-            let caller = AccountId::from([0x05; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+            let resultado = contrato.editar_producto(0, "Celular reacondicionado".to_string(), 800);
+            assert!(resultado.is_ok());
 
-            // Intenta modificar el rol
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
-            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+            let producto = contrato.obtener_producto(0).unwrap();
+            assert_eq!(producto.descripcion, "Celular reacondicionado");
+            assert_eq!(producto.precio, 800);
+            // El nombre y la categoría no cambian
+            assert_eq!(producto.nombre, "Celular");
         }
 
         #[ink::test]
-        fn modificar_rol_usuario_mismo_rol_falla() {
-            let mut contrato = setup_contract_con_vendedor();
-
-            // Cambia el caller a un usuario registrado como Comprador
+        fn editar_producto_ajeno_falla() {
+            let mut contrato = setup_producto_publicado();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.registrar_usuario(RolUsuario::Ambos).unwrap();
 
-            // Intenta cambiar a Comprador nuevamente
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
+            let resultado = contrato.editar_producto(0, "Otra".to_string(), 1);
             assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            assert!(matches!(contrato.reponer_stock(0, 5), Err(SistemaError::NoEsRolCorrecto)));
+            assert!(matches!(contrato.reducir_stock(0, 5), Err(SistemaError::NoEsRolCorrecto)));
+            assert!(matches!(contrato.pausar_producto(0), Err(SistemaError::NoEsRolCorrecto)));
+            assert!(matches!(contrato.dar_de_baja_producto(0), Err(SistemaError::NoEsRolCorrecto)));
+            assert_eq!(contrato.obtener_producto(0).unwrap().precio, 1000);
         }
 
         #[ink::test]
-        fn modificar_rol_usuario_no_puede_cambiar_a_vendedor_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn editar_producto_inexistente_falla() {
+            let mut contrato = setup_producto_publicado();
+            let resultado = contrato.editar_producto(7, "Desc".to_string(), 1);
+            assert!(matches!(resultado, Err(SistemaError::ProductosVacios)));
+        }
 
-            // Cambia el caller a un usuario registrado como Vendedor
+        #[ink::test]
+        fn orden_usa_el_precio_editado() {
+            let mut contrato = setup_producto_publicado();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            contrato.editar_producto(0, "Oferta".to_string(), 500).unwrap();
+
             test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Vendedor);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            pagar(1000);
+            assert!(matches!(contrato.crear_orden(0, 1), Err(SistemaError::PagoIncorrecto)));
+            pagar(500);
+            assert!(contrato.crear_orden(0, 1).is_ok());
+        }
 
-            // Intenta cambiar a Vendedor, lo cual no es permitido
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Vendedor);
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        #[ink::test]
+        fn reponer_stock_ok() {
+            let mut contrato = setup_producto_publicado();
+            let desde = test::recorded_events().count();
+
+            assert!(contrato.reponer_stock(0, 5).is_ok());
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 15);
+            let eventos = eventos_desde::<StockActualizado>(desde);
+            assert_eq!(eventos[0].stock, 15);
+
+            assert!(matches!(contrato.reponer_stock(0, 0), Err(SistemaError::CantidadInsuficiente)));
         }
 
         #[ink::test]
-        fn modificar_rol_usuario_no_puede_cambiar_a_comprador_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn reducir_stock_ok() {
+            let mut contrato = setup_producto_publicado();
+            let desde = test::recorded_events().count();
+
+            assert!(contrato.reducir_stock(0, 4).is_ok());
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 6);
+            let eventos = eventos_desde::<StockActualizado>(desde);
+            assert_eq!(eventos[0].stock, 6);
+
+            // Se puede dejar el producto sin stock
+            assert!(contrato.reducir_stock(0, 6).is_ok());
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 0);
+        }
 
-            // Cambia el caller a un usuario registrado como Comprador
+        #[ink::test]
+        fn reducir_stock_rechaza_mas_que_el_disponible() {
+            let mut contrato = setup_producto_publicado();
+            assert_eq!(contrato.reducir_stock(0, 11), Err(SistemaError::StockInsuficiente));
+            assert_eq!(contrato.reducir_stock(0, 0), Err(SistemaError::CantidadInsuficiente));
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 10);
+
+            // Reponer y reducir se compensan
+            contrato.reponer_stock(0, 3).unwrap();
+            assert!(contrato.reducir_stock(0, 13).is_ok());
+            assert_eq!(contrato.reducir_stock(0, 1), Err(SistemaError::StockInsuficiente));
+        }
+
+        #[ink::test]
+        fn producto_pausado_no_acepta_ordenes() {
+            let mut contrato = setup_producto_publicado();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            let vendedor = AccountId::from([0x01; 32]);
+            assert!(contrato.pausar_producto(0).is_ok());
+            assert_eq!(contrato.obtener_producto(0).unwrap().estado, EstadoProducto::Pausado);
+            // No se puede pausar dos veces
+            assert!(matches!(contrato.pausar_producto(0), Err(SistemaError::EstadoInvalido)));
 
-            // Intenta cambiar a Comprador, lo cual no es permitido
-            let resultado = contrato.modificar_rol_usuario(RolUsuario::Comprador);
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            pagar(1000);
+            assert!(matches!(contrato.crear_orden(0, 1), Err(SistemaError::ProductoNoDisponible)));
+
+            // Al reactivarlo vuelve a aceptar órdenes
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert!(contrato.reactivar_producto(0).is_ok());
+            assert!(matches!(contrato.reactivar_producto(0), Err(SistemaError::EstadoInvalido)));
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            pagar(1000);
+            assert!(contrato.crear_orden(0, 1).is_ok());
         }
 
-        // --- Publicación de productos ---
         #[ink::test]
-        fn publicar_producto_ok() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn producto_dado_de_baja_no_se_modifica_ni_se_vende() {
+            let mut contrato = setup_producto_publicado();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let desde = test::recorded_events().count();
+            assert!(contrato.dar_de_baja_producto(0).is_ok());
 
-            let resultado = contrato.publicar_producto(
-                "Celular".to_string(),
-                "Un buen celular".to_string(),
-                1000,
-                5,
-                "Tecnología".to_string(),
-            );
+            let eventos = eventos_desde::<EstadoProductoActualizado>(desde);
+            assert_eq!(eventos[0].estado_anterior, EstadoProducto::Activo);
+            assert_eq!(eventos[0].estado_nuevo, EstadoProducto::DadoDeBaja);
 
-            assert!(resultado.is_ok());
-            assert_eq!(contrato.productos.len(), 1);
+            assert!(matches!(contrato.editar_producto(0, "Desc".to_string(), 1), Err(SistemaError::ProductoNoDisponible)));
+            assert!(matches!(contrato.reponer_stock(0, 1), Err(SistemaError::ProductoNoDisponible)));
+            assert!(matches!(contrato.reducir_stock(0, 1), Err(SistemaError::ProductoNoDisponible)));
+            assert!(matches!(contrato.reactivar_producto(0), Err(SistemaError::ProductoNoDisponible)));
+            assert!(matches!(contrato.dar_de_baja_producto(0), Err(SistemaError::ProductoNoDisponible)));
 
-            let producto = &contrato.productos[0];
-            assert_eq!(producto.nombre, "Celular");
-            assert_eq!(producto.precio, 1000);
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            pagar(1000);
+            assert!(matches!(contrato.crear_orden(0, 1), Err(SistemaError::ProductoNoDisponible)));
         }
 
         #[ink::test]
-        fn publicar_producto_no_registrado_falla() {
-            let mut contrato = MarketplacePrincipal::new();
+        fn baja_de_producto_no_afecta_ordenes_existentes() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            contrato.dar_de_baja_producto(0).unwrap();
 
-            let caller = AccountId::from([0x02; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+            // La orden puede cancelarse y el stock vuelve al producto dado de baja
+            contrato.solicitar_cancelacion(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(contrato.aceptar_cancelacion(orden_id).is_ok());
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 10);
+        }
 
-            let resultado = contrato.publicar_producto(
-                "Producto".to_string(),
-                "Sin registro".to_string(),
-                500,
-                1,
-                "Otros".to_string(),
-            );
+        // --- Autocompra ---
+        #[ink::test]
+        fn crear_orden_sobre_producto_propio_falla() {
+            let mut contrato = setup_contract_con_vendedor();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.registrar_usuario(RolUsuario::Ambos).unwrap();
+            contrato.publicar_producto("Celular".to_string(), "Desc".to_string(), 1000, 5, "Tecnología".to_string()).unwrap();
 
-            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+            pagar(1000);
+            let resultado = contrato.crear_orden(0, 1);
+            assert!(matches!(resultado, Err(SistemaError::AutoCompraNoPermitida)));
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 5);
+            assert_eq!(contrato.siguiente_id_orden, 0);
+        }
+
+        // Orden previa en la que comprador y vendedor coinciden, creada sin pasar por `crear_orden`
+        fn setup_orden_propia(estado: EstadoOrden) -> (MarketplacePrincipal, u32) {
+            let mut contrato = setup_contract_con_vendedor();
+            let vendedor = AccountId::from([0x01; 32]);
+            contrato.publicar_producto("Celular".to_string(), "Desc".to_string(), 0, 5, "Tecnología".to_string()).unwrap();
+            let orden_id = contrato.crear_y_emitir_orden(Orden::new(0, vendedor, vendedor, 0, 1, 0)).unwrap();
+            let mut orden = contrato.ordenes.get(orden_id).unwrap();
+            orden.estado = estado;
+            contrato.ordenes.insert(orden_id, &orden);
+            (contrato, orden_id)
         }
 
         #[ink::test]
-        fn publicar_producto_no_es_vendedor_falla() {
-            let mut contrato = MarketplacePrincipal::new();
+        fn calificar_orden_propia_falla() {
+            let (mut contrato, orden_id) = setup_orden_propia(EstadoOrden::Recibida);
 
-            let caller = AccountId::from([0x03; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+            let resultado = contrato.calificar_vendedor(orden_id, 5);
+            assert!(matches!(resultado, Err(SistemaError::AutoCompraNoPermitida)));
+            let resultado = contrato.calificar_comprador(orden_id, 5);
+            assert!(matches!(resultado, Err(SistemaError::AutoCompraNoPermitida)));
 
-            let usuario = Usuario {
-                direccion: caller,
-                rol: RolUsuario::Comprador, // Rol no válido para publicar productos
-                reputacion_como_comprador: 0,
-                reputacion_como_vendedor: 0,
-            };
-            contrato.usuarios.insert(caller, &usuario);
+            let usuario = contrato.obtener_usuario(AccountId::from([0x01; 32])).unwrap();
+            assert_eq!(usuario.reputacion_como_vendedor.cantidad_calificaciones, 0);
+            assert_eq!(usuario.reputacion_como_comprador.cantidad_calificaciones, 0);
+        }
 
-            let resultado = contrato.publicar_producto(
-                "Producto".to_string(),
-                "No autorizado".to_string(),
-                100,
-                2,
-                "Otros".to_string(),
-            );
+        #[ink::test]
+        fn abrir_disputa_sobre_orden_propia_falla() {
+            let (mut contrato, orden_id) = setup_orden_propia(EstadoOrden::Enviada);
 
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            let resultado = contrato.abrir_disputa(orden_id, Hash::from([0x07; 32]));
+            assert!(matches!(resultado, Err(SistemaError::AutoCompraNoPermitida)));
+            assert_eq!(contrato.obtener_orden(orden_id).unwrap().estado, EstadoOrden::Enviada);
         }
 
+        // --- Índices por cuenta ---
         #[ink::test]
-        fn publicar_producto_cantidad_cero_falla() {
+        fn productos_de_vendedor_lista_solo_los_propios() {
             let mut contrato = setup_contract_con_vendedor();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
+            contrato.publicar_producto("Celular".to_string(), "Desc".to_string(), 1000, 5, "Tecnología".to_string()).unwrap();
 
-            let resultado = contrato.publicar_producto(
-                "Producto".to_string(),
-                "Cantidad cero".to_string(),
-                100,
-                0, // Cantidad inválida
-                "Otros".to_string(),
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contrato.registrar_usuario(RolUsuario::Vendedor).unwrap();
+            contrato.publicar_producto("Mesa".to_string(), "Desc".to_string(), 500, 2, "Hogar".to_string()).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.publicar_producto("Tablet".to_string(), "Desc".to_string(), 800, 3, "Tecnología".to_string()).unwrap();
+
+            let propios = contrato.productos_de_vendedor(vendedor, 0, LIMITE_PAGINA).elementos;
+            assert_eq!(propios.iter().map(|p| p.id).collect::<Vec<_>>(), vec![0, 2]);
+            let de_charlie = contrato.productos_de_vendedor(accounts.charlie, 0, LIMITE_PAGINA).elementos;
+            assert_eq!(de_charlie.len(), 1);
+            assert_eq!(de_charlie[0].nombre, "Mesa");
+            assert_eq!(
+                contrato.productos_de_vendedor(accounts.django, 0, LIMITE_PAGINA),
+                Pagina { elementos: vec![], siguiente: None }
             );
-
-            assert!(matches!(resultado, Err(SistemaError::CantidadInsuficiente)));
         }
 
-        // --- Compra y órdenes ---
         #[ink::test]
-        fn crear_orden_ok() {
+        fn productos_de_vendedor_se_pagina_con_cursor() {
             let mut contrato = setup_contract_con_vendedor();
+            let vendedor = AccountId::from([0x01; 32]);
+            for i in 0..5 {
+                contrato.publicar_producto(format!("P{}", i), "Desc".to_string(), 100, 1, "Varios".to_string()).unwrap();
+            }
 
-            // Publica un producto y obtiene el ID
-            let _ = contrato.publicar_producto(
-                "Laptop".to_string(),
-                "Una laptop potente".to_string(),
-                2000,
-                10,
-                "Tecnología".to_string(),
-            );
+            let primera = contrato.productos_de_vendedor(vendedor, 0, 2);
+            assert_eq!(primera.elementos.iter().map(|p| p.id).collect::<Vec<_>>(), vec![0, 1]);
+            assert_eq!(primera.siguiente, Some(2));
+            let ultima = contrato.productos_de_vendedor(vendedor, 4, 2);
+            assert_eq!(ultima.elementos.iter().map(|p| p.id).collect::<Vec<_>>(), vec![4]);
+            assert_eq!(ultima.siguiente, None);
+            // El límite pedido se acota a `LIMITE_PAGINA`
+            assert_eq!(contrato.productos_de_vendedor(vendedor, 0, u32::MAX).elementos.len(), 5);
+        }
 
-            // Cambia el caller a un usuario comprador y regístralo
+        #[ink::test]
+        fn ordenes_por_cuenta_se_indexan_por_rol() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            let vendedor = AccountId::from([0x01; 32]);
 
-            // El producto publicado tendrá id = 0 (si es el primero)
-            let resultado = contrato.crear_orden(0, 2);
+            let como_comprador = contrato.ordenes_como_comprador(accounts.bob, 0, LIMITE_PAGINA).elementos;
+            assert_eq!(como_comprador.len(), 1);
+            assert_eq!(como_comprador[0].id, orden_id);
+            assert!(contrato.ordenes_como_vendedor(accounts.bob, 0, LIMITE_PAGINA).elementos.is_empty());
 
-            assert!(resultado.is_ok());
-            let orden_id = resultado.unwrap();
-            assert_eq!(contrato.ordenes.len(), 1);
+            let como_vendedor = contrato.ordenes_como_vendedor(vendedor, 0, LIMITE_PAGINA).elementos;
+            assert_eq!(como_vendedor.len(), 1);
+            assert_eq!(como_vendedor[0].comprador, accounts.bob);
+            assert!(contrato.ordenes_como_comprador(vendedor, 0, LIMITE_PAGINA).elementos.is_empty());
 
-            let orden = &contrato.ordenes[0];
-            assert_eq!(orden.id, orden_id);
-            assert_eq!(orden.cantidad, 2);
-            assert_eq!(orden.estado, EstadoOrden::Pendiente);
+            // Las consultas reflejan el estado actual de la orden
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            let como_comprador = contrato.ordenes_como_comprador(accounts.bob, 0, LIMITE_PAGINA).elementos;
+            assert_eq!(como_comprador[0].estado, EstadoOrden::Enviada);
         }
 
         #[ink::test]
-        fn crear_orden_no_registrado_falla() {
-            let mut contrato = MarketplacePrincipal::new();
-
-            let caller = AccountId::from([0x04; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+        fn ordenes_por_cuenta_se_paginan_con_cursor() {
+            let (mut contrato, primera) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
+            for _ in 0..2 {
+                pagar(1000);
+                contrato.crear_orden(0, 1).unwrap();
+            }
 
-            let resultado = contrato.crear_orden(0, 1);
+            let pagina = contrato.ordenes_como_comprador(accounts.bob, 1, 1);
+            assert_eq!(pagina.elementos[0].id, primera + 1);
+            assert_eq!(pagina.siguiente, Some(2));
+            let pagina = contrato.ordenes_como_vendedor(vendedor, 2, 5);
+            assert_eq!(pagina.elementos.len(), 1);
+            assert_eq!(pagina.elementos[0].id, primera + 2);
+            assert_eq!(pagina.siguiente, None);
+            // Un cursor fuera de rango devuelve una página vacía
+            assert!(contrato.ordenes_como_vendedor(vendedor, 10, 5).elementos.is_empty());
+        }
 
-            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+        // --- Propiedad y pausa ---
+        #[ink::test]
+        fn new_asigna_propietario_al_caller() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let contrato = MarketplacePrincipal::new();
+            assert_eq!(contrato.propietario(), accounts.charlie);
+            assert!(!contrato.esta_pausado());
         }
 
         #[ink::test]
-        fn verificar_registro_antes_de_crear_orden() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn transferir_propiedad_ok() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contrato = MarketplacePrincipal::new();
 
-            // Publica un producto
-            let _ = contrato.publicar_producto(
-                "Producto Test".to_string(),
-                "Descripción Test".to_string(),
-                1000,
-                10,
-                "Test".to_string(),
-            );
+            assert!(contrato.transferir_propiedad(accounts.charlie).is_ok());
+            assert_eq!(contrato.propietario(), accounts.charlie);
 
-            // Cambia el caller a un usuario NO registrado
-            let nuevo_usuario = AccountId::from([0x99; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(nuevo_usuario);
+            // El propietario anterior pierde sus privilegios
+            let resultado = contrato.transferir_propiedad(accounts.alice);
+            assert_eq!(resultado, Err(SistemaError::NoEsPropietario));
+            assert_eq!(contrato.agregar_administrador(accounts.bob), Err(SistemaError::NoEsPropietario));
 
-            // Verifica que el usuario no está registrado
-            assert!(!contrato.esta_registrado(nuevo_usuario));
-            assert!(contrato.obtener_usuario(nuevo_usuario).is_none());
+            let eventos = eventos_desde::<PropiedadTransferida>(0);
+            assert_eq!(eventos[0].propietario_anterior, accounts.alice);
+            assert_eq!(eventos[0].propietario_nuevo, accounts.charlie);
+        }
 
-            // Intenta crear una orden y falla porque no está registrado
-            let resultado = contrato.crear_orden(0, 1);
-            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+        #[ink::test]
+        fn administradores_solo_los_gestiona_el_propietario() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contrato = MarketplacePrincipal::new();
 
-            // Registra al usuario como comprador
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            assert!(contrato.agregar_administrador(accounts.bob).is_ok());
+            assert!(contrato.agregar_administrador(accounts.bob).is_ok());
+            assert!(contrato.es_administrador(accounts.bob));
 
-            // Verifica que ahora está registrado
-            assert!(contrato.esta_registrado(nuevo_usuario));
-            let usuario_info = contrato.obtener_usuario(nuevo_usuario).unwrap();
-            assert_eq!(usuario_info.rol, RolUsuario::Comprador);
+            // Un administrador no puede sumar a otros
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contrato.agregar_administrador(accounts.charlie), Err(SistemaError::NoEsPropietario));
 
-            // Ahora puede crear una orden exitosamente
-            let resultado = contrato.crear_orden(0, 1);
-            assert!(resultado.is_ok());
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contrato.quitar_administrador(accounts.bob).is_ok());
+            assert!(!contrato.es_administrador(accounts.bob));
+            assert_eq!(test::recorded_events().count(), 2);
         }
 
         #[ink::test]
-        fn crear_orden_no_es_comprador_falla() {
-            let mut contrato = MarketplacePrincipal::new();
-
-            let caller = AccountId::from([0x05; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(caller);
+        fn pausar_bloquea_mensajes_que_modifican_estado() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
 
-            let usuario = Usuario {
-                direccion: caller,
-                rol: RolUsuario::Vendedor, // Rol no válido para crear órdenes
-                reputacion_como_comprador: 0,
-                reputacion_como_vendedor: 0,
-            };
-            contrato.usuarios.insert(caller, &usuario);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert!(contrato.pausar().is_ok());
+            assert!(contrato.esta_pausado());
+            assert_eq!(contrato.marcar_orden_como_enviada(orden_id), Err(SistemaError::ContratoPausado));
+            assert_eq!(contrato.reponer_stock(0, 5), Err(SistemaError::ContratoPausado));
+            assert_eq!(contrato.reducir_stock(0, 1), Err(SistemaError::ContratoPausado));
 
-            // Primero, publica un producto para poder comprarlo
-            let _ = contrato.publicar_producto(
-                "Tablet".to_string(),
-                "Una tablet versátil".to_string(),
-                1500,
-                7,
-                "Tecnología".to_string(),
-            );
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            pagar(1000);
+            assert_eq!(contrato.crear_orden(0, 1), Err(SistemaError::ContratoPausado));
+            assert_eq!(contrato.solicitar_cancelacion(orden_id), Err(SistemaError::ContratoPausado));
 
-            let resultado = contrato.crear_orden(0, 1);
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(contrato.registrar_usuario(RolUsuario::Comprador), Err(SistemaError::ContratoPausado));
 
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            // Las consultas siguen disponibles
+            assert_eq!(contrato.obtener_orden(orden_id).unwrap().estado, EstadoOrden::Pendiente);
+            assert_eq!(contrato.listar_productos(0, 10).elementos.len(), 1);
+            assert!(contrato.esta_registrado(accounts.bob));
         }
 
         #[ink::test]
-        fn crear_orden_con_rol_ambos_ok() {
+        fn pausar_bloquea_comisiones_y_tokens_pero_no_la_gestion_del_contrato() {
             let mut contrato = setup_contract_con_vendedor();
-
-            // Publica un producto
-            let _ = contrato.publicar_producto(
-                "Producto Test".to_string(),
-                "Descripción Test".to_string(),
-                1000,
-                10,
-                "Test".to_string(),
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let propietario = AccountId::from([0x01; 32]);
+            contrato.fijar_comision(500).unwrap();
+            contrato.pausar().unwrap();
+
+            assert_eq!(contrato.fijar_comision(100), Err(SistemaError::ContratoPausado));
+            assert_eq!(contrato.retirar_comisiones(propietario, 0), Err(SistemaError::ContratoPausado));
+            assert_eq!(
+                contrato.retirar_comisiones_en_moneda(Moneda::Psp22(token()), propietario, 0),
+                Err(SistemaError::ContratoPausado)
             );
+            assert_eq!(contrato.permitir_token(token()), Err(SistemaError::ContratoPausado));
+            assert_eq!(contrato.quitar_token(token()), Err(SistemaError::ContratoPausado));
+            assert_eq!(contrato.comision_bps(), 500);
+            assert!(!contrato.es_token_permitido(token()));
+
+            // La gestión de administradores y de la propiedad sigue disponible
+            assert!(contrato.agregar_administrador(accounts.django).is_ok());
+            assert!(contrato.quitar_administrador(accounts.django).is_ok());
+            assert!(contrato.transferir_propiedad(accounts.charlie).is_ok());
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert!(contrato.reanudar().is_ok());
+            assert!(contrato.permitir_token(token()).is_ok());
+        }
 
-            // Cambia el caller a un usuario con rol Ambos
+        #[ink::test]
+        fn reanudar_vuelve_a_habilitar_mensajes() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
-            let _ = contrato.registrar_usuario(RolUsuario::Ambos);
+            let vendedor = AccountId::from([0x01; 32]);
 
-            // Debería poder crear una orden exitosamente
-            let resultado = contrato.crear_orden(0, 1);
-            assert!(resultado.is_ok());
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.agregar_administrador(accounts.django).unwrap();
+
+            // Un administrador puede pausar y reanudar
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            contrato.pausar().unwrap();
+            assert_eq!(contrato.pausar(), Err(SistemaError::ContratoPausado));
+            assert!(contrato.reanudar().is_ok());
+            assert_eq!(contrato.reanudar(), Err(SistemaError::ContratoNoPausado));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert!(contrato.marcar_orden_como_enviada(orden_id).is_ok());
         }
 
         #[ink::test]
-        fn crear_orden_cantidad_insuficiente_falla() {
+        fn pausar_sin_ser_administrador_falla() {
             let mut contrato = setup_contract_con_vendedor();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
 
-            // Primero, publica un producto con cantidad insuficiente
-            let _ = contrato.publicar_producto(
-                "Smartwatch".to_string(),
-                "Un smartwatch elegante".to_string(),
-                500,
-                2, // Solo hay 2 disponibles
-                "Tecnología".to_string(),
-            );
+            assert_eq!(contrato.pausar(), Err(SistemaError::NoEsAdministrador));
+            assert_eq!(contrato.reanudar(), Err(SistemaError::NoEsAdministrador));
+            assert!(!contrato.esta_pausado());
+        }
 
-            // Cambia el caller a un usuario comprador y regístralo
+        // --- Actualización de código ---
+        // `set_code_hash` no existe fuera de la cadena: la actualización exitosa y
+        // `CodigoInvalido` se prueban en los e2e de `psp22_mock`
+        #[ink::test]
+        fn actualizar_codigo_sin_ser_propietario_falla() {
+            let mut contrato = MarketplacePrincipal::new();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
             test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
 
-            // Intenta crear una orden de compra de 3 unidades
-            let resultado = contrato.crear_orden(0, 3); // Compra 3 unidades
+            let resultado = contrato.actualizar_codigo(Hash::from([0x42; 32]));
+            assert_eq!(resultado, Err(SistemaError::NoEsPropietario));
+        }
 
-            assert!(matches!(resultado, Err(SistemaError::StockInsuficiente)));
+        #[ink::test]
+        fn contrato_nuevo_no_requiere_migracion() {
+            let mut contrato = MarketplacePrincipal::new();
+            assert_eq!(contrato.version_almacenamiento(), VERSION_ALMACENAMIENTO);
+            assert_eq!(contrato.migrar(LIMITE_PAGINA), Err(SistemaError::SinMigracionPendiente));
         }
 
         #[ink::test]
-        fn crear_orden_cantidad_cero_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn migracion_pendiente_bloquea_mensajes_hasta_migrar() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let propietario = AccountId::from([0x01; 32]);
+            // Simula el estado que deja un código nuevo con un formato de registros más reciente
+            escribir_orden_v3(&contrato, &contrato.obtener_orden(orden_id).unwrap());
+            contrato.version_almacenamiento = 3;
 
-            // Publica un producto
-            let _ = contrato.publicar_producto(
-                "Producto Test".to_string(),
-                "Descripción Test".to_string(),
-                1000,
-                10,
-                "Test".to_string(),
-            );
+            test::set_caller::<ink::env::DefaultEnvironment>(propietario);
+            assert_eq!(contrato.marcar_orden_como_enviada(orden_id), Err(SistemaError::MigracionPendiente));
+            // Las consultas de registros que no cambiaron de formato siguen disponibles
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 8);
 
-            // Cambia el caller a un usuario comprador y regístralo
-            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
             test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            assert_eq!(contrato.migrar(LIMITE_PAGINA), Err(SistemaError::NoEsPropietario));
+
+            test::set_caller::<ink::env::DefaultEnvironment>(propietario);
+            let desde = test::recorded_events().count();
+            assert_eq!(contrato.migrar(LIMITE_PAGINA), Ok(true));
+            assert_eq!(contrato.version_almacenamiento(), VERSION_ALMACENAMIENTO);
+            let migrado = eventos_desde::<AlmacenamientoMigrado>(desde).remove(0);
+            assert_eq!(migrado.version_anterior, 3);
+
+            // Los registros existentes siguen accesibles y operables
+            assert!(contrato.marcar_orden_como_enviada(orden_id).is_ok());
+            assert!(contrato.esta_registrado(accounts.bob));
+        }
 
-            // Intenta crear una orden con cantidad 0
-            let resultado = contrato.crear_orden(0, 0);
+        // --- Comisiones ---
+        #[ink::test]
+        fn fijar_comision_ok() {
+            let mut contrato = MarketplacePrincipal::new();
+            assert_eq!(contrato.comision_bps(), 0);
+            assert!(contrato.fijar_comision(250).is_ok());
+            assert_eq!(contrato.comision_bps(), 250);
 
-            assert!(matches!(resultado, Err(SistemaError::CantidadInsuficiente)));
+            assert_eq!(contrato.fijar_comision(COMISION_MAXIMA_BPS + 1), Err(SistemaError::ComisionInvalida));
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contrato.fijar_comision(0), Err(SistemaError::NoEsPropietario));
+            assert_eq!(contrato.comision_bps(), 250);
         }
 
         #[ink::test]
-        fn crear_orden_descuenta_stock() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn orden_recibida_descuenta_comision() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.fijar_comision(500).unwrap();
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
 
-            // Primero, publica un producto con cantidad suficiente
-            let _ = contrato.publicar_producto(
-                "Auriculares".to_string(),
-                "Auriculares inalámbricos".to_string(),
-                800,
-                10, // 10 disponibles
-                "Tecnología".to_string(),
-            );
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.marcar_como_recibida(orden_id).unwrap();
 
-            // Cambia el caller a un usuario comprador y regístralo
+            // 5% de 2000 queda en la tesorería y registrado en la orden
+            assert_eq!(contrato.saldo_de(vendedor), 1900);
+            assert_eq!(contrato.tesoreria(), 100);
+            assert_eq!(contrato.obtener_orden(orden_id).unwrap().comision, 100);
+        }
+
+        #[ink::test]
+        fn cancelacion_y_reembolso_no_cobran_comision() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.fijar_comision(500).unwrap();
+            contrato.solicitar_cancelacion(orden_id).unwrap();
             test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            contrato.aceptar_cancelacion(orden_id).unwrap();
 
-            // Crea una orden de compra
-            let resultado = contrato.crear_orden(0, 3); // Compra 3 unidades
+            assert_eq!(contrato.tesoreria(), 0);
+        }
 
-            assert!(resultado.is_ok());
-            let _orden_id = resultado.unwrap();
-            assert_eq!(contrato.ordenes.len(), 1);
+        #[ink::test]
+        fn disputa_dividida_cobra_comision_sobre_la_parte_del_vendedor() {
+            let (mut contrato, orden_id) = setup_orden_enviada();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.fijar_comision(1_000).unwrap();
 
-            // Verifica que el stock se haya descontado correctamente
-            let producto = &contrato.productos[0];
-            assert_eq!(producto.cantidad, 7); // Debería quedar 7 después de la compra
-        }
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.abrir_disputa(orden_id, Hash::from([0x07; 32])).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            contrato
+                .resolver_disputa(orden_id, ResolucionDisputa::Dividir { monto_comprador: 1000 })
+                .unwrap();
 
-        
+            assert_eq!(contrato.saldo_de(vendedor), 900);
+            assert_eq!(contrato.tesoreria(), 100);
+            assert_eq!(contrato.obtener_orden(orden_id).unwrap().comision, 100);
+        }
 
-        // --- Gestión de órdenes ---
-        /* ESTOS QUE ESTAN COMENTADOS FALLAN
         #[ink::test]
-        fn marcar_orden_como_enviada_ok() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn retirar_comisiones_ok() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let propietario = AccountId::from([0x01; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(propietario);
+            contrato.fijar_comision(1_000).unwrap();
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.marcar_como_recibida(orden_id).unwrap();
+            assert_eq!(contrato.tesoreria(), 200);
+
+            // Solo el propietario puede retirar, y no más de lo acumulado
+            assert_eq!(contrato.retirar_comisiones(accounts.bob, 50), Err(SistemaError::NoEsPropietario));
+            test::set_caller::<ink::env::DefaultEnvironment>(propietario);
+            assert_eq!(
+                contrato.retirar_comisiones(accounts.charlie, 201),
+                Err(SistemaError::ComisionesInsuficientes)
+            );
 
-            // Primero, crea una orden
-            let orden_id = contrato.crear_orden(0, 1).unwrap();
+            let saldo_destino = balance_de(accounts.charlie);
+            assert!(contrato.retirar_comisiones(accounts.charlie, 150).is_ok());
+            assert_eq!(balance_de(accounts.charlie), saldo_destino + 150);
+            assert_eq!(contrato.tesoreria(), 50);
+        }
 
-            // Marca la orden como enviada
-            let resultado = contrato.marcar_orden_como_enviada(orden_id);
+        #[ink::test]
+        fn calcular_comision_no_desborda() {
+            assert_eq!(MarketplacePrincipal::calcular_comision(2000, 250), 50);
+            assert_eq!(MarketplacePrincipal::calcular_comision(99, 100), 0);
+            assert_eq!(MarketplacePrincipal::calcular_comision(Balance::MAX, BPS_TOTAL), Balance::MAX);
+        }
 
-            assert!(resultado.is_ok());
-            let orden = &contrato.ordenes[0];
-            assert_eq!(orden.estado, EstadoOrden::Enviada);
+        // --- Retiro de fondos ---
+        #[ink::test]
+        fn retirar_fondos_ok() {
+            let (mut contrato, _) = setup_orden_recibida();
+            let vendedor = AccountId::from([0x01; 32]);
+            let cuenta_contrato = test::callee::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            let saldo_vendedor = balance_de(vendedor);
+            let saldo_contrato = balance_de(cuenta_contrato);
+
+            // Se puede retirar en partes
+            assert!(contrato.retirar_fondos(1500).is_ok());
+            assert_eq!(contrato.saldo_de(vendedor), 500);
+            assert!(contrato.retirar_fondos(500).is_ok());
+            assert_eq!(contrato.saldo_de(vendedor), 0);
+
+            assert_eq!(balance_de(vendedor), saldo_vendedor + 2000);
+            assert_eq!(balance_de(cuenta_contrato), saldo_contrato - 2000);
+            let retiros = eventos_desde::<FondosRetirados>(test::recorded_events().count() - 1);
+            assert_eq!(retiros[0].cuenta, vendedor);
+            assert_eq!(retiros[0].monto, 500);
         }
 
         #[ink::test]
-        fn marcar_orden_como_enviada_usuario_incorrecto_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn retirar_fondos_saldo_insuficiente_falla() {
+            let (mut contrato, _) = setup_orden_recibida();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
 
-            // Primero, crea una orden
-            let orden_id = contrato.crear_orden(0, 1).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert_eq!(contrato.retirar_fondos(2001), Err(SistemaError::SaldoInsuficiente));
+            assert_eq!(contrato.saldo_de(vendedor), 2000);
 
-            // Simula que otro usuario intenta marcar la orden como enviada
-            let otro_usuario = AccountId::from([0x06; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(otro_usuario);
+            // El comprador no tiene saldo que retirar
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contrato.retirar_fondos(1), Err(SistemaError::SaldoInsuficiente));
+        }
 
-            let resultado = contrato.marcar_orden_como_enviada(orden_id);
+        #[ink::test]
+        fn saldo_acumula_varias_ordenes() {
+            let (mut contrato, orden_id) = setup_orden_recibida();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
 
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            pagar(3000);
+            let segunda = contrato.crear_orden(0, 3).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.marcar_orden_como_enviada(segunda).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.marcar_como_recibida(segunda).unwrap();
+
+            assert_ne!(orden_id, segunda);
+            assert_eq!(contrato.saldo_de(vendedor), 5000);
         }
 
         #[ink::test]
-        fn marcar_como_recibida_ok() {
-            let mut contrato = setup_contract_con_vendedor();
-
-            // Primero, crea y envía una orden
-            let orden_id = contrato.crear_orden(0, 1).unwrap();
-            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+        fn migrar_convierte_registros_de_la_version_1() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let producto = contrato.obtener_producto(0).unwrap();
+            let orden = contrato.obtener_orden(orden_id).unwrap();
+
+            // Reescribe los registros con el formato de la versión 1, sin moneda
+            let anterior = v1::Producto {
+                id: producto.id,
+                nombre: producto.nombre.clone(),
+                descripcion: producto.descripcion.clone(),
+                precio: producto.precio,
+                cantidad: producto.cantidad,
+                categoria: producto.categoria.clone(),
+                vendedor: producto.vendedor,
+                unidades_vendidas: producto.unidades_vendidas,
+                estado: producto.estado.clone(),
+            };
+            ink::env::set_contract_storage(&(clave(&contrato.productos), 0u32), &anterior);
+            let anterior = v1::Orden {
+                id: orden.id,
+                comprador: orden.comprador,
+                vendedor: orden.vendedor,
+                producto_id: orden.lineas[0].producto_id,
+                cantidad: orden.lineas[0].cantidad,
+                monto: orden.monto,
+                estado: orden.estado.clone(),
+                comprador_califico: orden.comprador_califico,
+                puntaje_al_vendedor: orden.puntaje_al_vendedor,
+                vendedor_califico: orden.vendedor_califico,
+                comprador_acepta_cancelacion: orden.comprador_acepta_cancelacion,
+                vendedor_acepta_cancelacion: orden.vendedor_acepta_cancelacion,
+                disputa: orden.disputa.clone(),
+            };
+            ink::env::set_contract_storage(&(clave(&contrato.ordenes), orden_id), &anterior);
+            contrato.version_almacenamiento = 1;
 
-            let resultado = contrato.marcar_como_recibida(orden_id);
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            assert_eq!(contrato.migrar(LIMITE_PAGINA), Ok(true));
+            assert_eq!(contrato.obtener_producto(0), Some(producto));
+            assert_eq!(contrato.obtener_orden(orden_id), Some(orden));
+        }
 
-            assert!(resultado.is_ok());
-            let orden = &contrato.ordenes[0];
-            assert_eq!(orden.estado, EstadoOrden::Recibida);
+        #[ink::test]
+        fn migrar_convierte_ordenes_de_la_version_2() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let orden = contrato.obtener_orden(orden_id).unwrap();
+            escribir_orden_v2(&contrato, &orden);
+            contrato.version_almacenamiento = 2;
+
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            assert_eq!(contrato.migrar(LIMITE_PAGINA), Ok(true));
+            let migrada = contrato.obtener_orden(orden_id).unwrap();
+            assert_eq!(migrada, orden);
+            assert_eq!(migrada.lineas, vec![LineaOrden { producto_id: 0, cantidad: 2, monto: 2000 }]);
         }
 
         #[ink::test]
-        fn marcar_como_recibida_usuario_incorrecto_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn migrar_en_varios_lotes_retoma_donde_quedo() {
+            let (mut contrato, primera) = setup_orden_pendiente();
+            let propietario = AccountId::from([0x01; 32]);
+            pagar(1000);
+            contrato.crear_orden(0, 1).unwrap();
+            pagar(1000);
+            contrato.crear_orden(0, 1).unwrap();
+            let ordenes: Vec<Orden> = (primera..primera + 3).map(|id| contrato.obtener_orden(id).unwrap()).collect();
+            for orden in &ordenes {
+                escribir_orden_v2(&contrato, orden);
+            }
+            contrato.version_almacenamiento = 2;
+            test::set_caller::<ink::env::DefaultEnvironment>(propietario);
+            let desde = test::recorded_events().count();
+
+            // Cada paso recorre las tres órdenes, de a dos por llamada
+            assert_eq!(contrato.migrar(2), Ok(false));
+            assert_eq!(contrato.progreso_migracion(), Some(ProgresoMigracion { paso: 3, posicion: 2 }));
+            assert_eq!(contrato.migrar(2), Ok(false));
+            assert_eq!(contrato.progreso_migracion(), Some(ProgresoMigracion { paso: 4, posicion: 1 }));
+            // El paso a la versión 5 recorre los dos usuarios, el producto y las tres órdenes
+            assert_eq!(contrato.migrar(2), Ok(false));
+            assert_eq!(contrato.progreso_migracion(), Some(ProgresoMigracion { paso: 5, posicion: 0 }));
+            assert_eq!(contrato.migrar(4), Ok(false));
+            assert_eq!(contrato.progreso_migracion(), Some(ProgresoMigracion { paso: 5, posicion: 4 }));
+            assert_eq!(contrato.migrar(4), Ok(false));
+            assert_eq!(contrato.progreso_migracion(), Some(ProgresoMigracion { paso: 6, posicion: 2 }));
+            // Los mensajes siguen bloqueados hasta el último lote
+            assert_eq!(contrato.version_almacenamiento(), 2);
+            assert_eq!(contrato.marcar_orden_como_enviada(primera), Err(SistemaError::MigracionPendiente));
+            assert!(eventos_desde::<AlmacenamientoMigrado>(desde).is_empty());
+
+            assert_eq!(contrato.migrar(2), Ok(true));
+            assert_eq!(contrato.version_almacenamiento(), VERSION_ALMACENAMIENTO);
+            assert_eq!(contrato.progreso_migracion(), None);
+            assert_eq!(eventos_desde::<AlmacenamientoMigrado>(desde).remove(0).version_anterior, 2);
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(contrato.ordenes_como_comprador(accounts.bob, 0, LIMITE_PAGINA).elementos, ordenes);
+            assert_eq!(contrato.migrar(2), Err(SistemaError::SinMigracionPendiente));
+        }
 
-            // Primero, crea y envía una orden
-            let orden_id = contrato.crear_orden(0, 1).unwrap();
+        // Reescribe una orden de un solo producto con el formato de la versión 2
+        fn escribir_orden_v2(contrato: &MarketplacePrincipal, orden: &Orden) {
+            let anterior = v2::Orden {
+                id: orden.id,
+                comprador: orden.comprador,
+                vendedor: orden.vendedor,
+                producto_id: orden.lineas[0].producto_id,
+                cantidad: orden.lineas[0].cantidad,
+                monto: orden.monto,
+                estado: orden.estado.clone(),
+                comprador_califico: orden.comprador_califico,
+                puntaje_al_vendedor: orden.puntaje_al_vendedor,
+                vendedor_califico: orden.vendedor_califico,
+                comprador_acepta_cancelacion: orden.comprador_acepta_cancelacion,
+                vendedor_acepta_cancelacion: orden.vendedor_acepta_cancelacion,
+                disputa: orden.disputa.clone(),
+                moneda: orden.moneda,
+            };
+            ink::env::set_contract_storage(&(clave(&contrato.ordenes), orden.id), &anterior);
+        }
+
+        #[ink::test]
+        fn migrar_fija_los_plazos_de_las_ordenes_de_la_version_3() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let vendedor = AccountId::from([0x01; 32]);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
             contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            escribir_orden_v3(&contrato, &contrato.obtener_orden(orden_id).unwrap());
+            contrato.version_almacenamiento = 3;
+
+            // Los plazos de las órdenes existentes corren desde la migración
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(5_000);
+            assert_eq!(contrato.migrar(LIMITE_PAGINA), Ok(true));
+            let migrada = contrato.obtener_orden(orden_id).unwrap();
+            assert_eq!(migrada.creada_en, 5_000);
+            assert_eq!(migrada.enviada_en, Some(5_000));
+            assert_eq!(migrada.estado, EstadoOrden::Enviada);
+        }
 
-            // Simula que otro usuario intenta marcar la orden como recibida
-            let otro_usuario = AccountId::from([0x07; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(otro_usuario);
+        // Reescribe una orden con el formato de la versión 3, sin momentos de creación y envío
+        fn escribir_orden_v3(contrato: &MarketplacePrincipal, orden: &Orden) {
+            let anterior = v3::Orden {
+                id: orden.id,
+                comprador: orden.comprador,
+                vendedor: orden.vendedor,
+                lineas: orden.lineas.clone(),
+                monto: orden.monto,
+                estado: orden.estado.clone(),
+                comprador_califico: orden.comprador_califico,
+                puntaje_al_vendedor: orden.puntaje_al_vendedor,
+                vendedor_califico: orden.vendedor_califico,
+                comprador_acepta_cancelacion: orden.comprador_acepta_cancelacion,
+                vendedor_acepta_cancelacion: orden.vendedor_acepta_cancelacion,
+                disputa: orden.disputa.clone(),
+                moneda: orden.moneda,
+            };
+            ink::env::set_contract_storage(&(clave(&contrato.ordenes), orden.id), &anterior);
+        }
 
-            let resultado = contrato.marcar_como_recibida(orden_id);
+        #[ink::test]
+        fn migrar_deja_sin_comision_las_ordenes_de_la_version_5() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let orden = contrato.obtener_orden(orden_id).unwrap();
+            escribir_orden_v5(&contrato, &orden);
+            contrato.version_almacenamiento = 5;
+
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            assert_eq!(contrato.migrar(LIMITE_PAGINA), Ok(true));
+            let migrada = contrato.obtener_orden(orden_id).unwrap();
+            assert_eq!(migrada, orden);
+            assert_eq!(migrada.comision, 0);
+        }
 
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+        // Reescribe una orden con el formato de las versiones 4 y 5, sin la comisión cobrada
+        fn escribir_orden_v5(contrato: &MarketplacePrincipal, orden: &Orden) {
+            let anterior = v5::Orden {
+                id: orden.id,
+                comprador: orden.comprador,
+                vendedor: orden.vendedor,
+                lineas: orden.lineas.clone(),
+                monto: orden.monto,
+                estado: orden.estado.clone(),
+                comprador_califico: orden.comprador_califico,
+                puntaje_al_vendedor: orden.puntaje_al_vendedor,
+                vendedor_califico: orden.vendedor_califico,
+                comprador_acepta_cancelacion: orden.comprador_acepta_cancelacion,
+                vendedor_acepta_cancelacion: orden.vendedor_acepta_cancelacion,
+                disputa: orden.disputa.clone(),
+                moneda: orden.moneda,
+                creada_en: orden.creada_en,
+                enviada_en: orden.enviada_en,
+            };
+            ink::env::set_contract_storage(&(clave(&contrato.ordenes), orden.id), &anterior);
+        }
+
+        // Clave de almacenamiento de un `Mapping`, para escribir registros en un formato anterior
+        fn clave<C, V: Packed, K: StorageKey>(_registros: &Mapping<C, V, K>) -> u32 {
+            K::KEY
         }
 
         #[ink::test]
-        fn transicion_estado_invalida_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn migrar_reconstruye_los_indices_de_la_version_4() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
+            // Deja los índices como en la versión 4: una lista de ids bajo la cuenta sola
+            let listas = [
+                (clave(&contrato.productos_por_vendedor), vendedor),
+                (clave(&contrato.ordenes_por_comprador), accounts.bob),
+                (clave(&contrato.ordenes_por_vendedor), vendedor),
+            ];
+            for (indice, cuenta) in listas {
+                ink::env::set_contract_storage(&(indice, cuenta), &vec![0u32]);
+            }
+            contrato.cantidad_productos_por_vendedor.remove(vendedor);
+            contrato.cantidad_ordenes_por_comprador.remove(accounts.bob);
+            contrato.cantidad_ordenes_por_vendedor.remove(vendedor);
+            escribir_orden_v5(&contrato, &contrato.obtener_orden(orden_id).unwrap());
+            contrato.version_almacenamiento = 4;
 
-            // Primero, crea una orden
-            let orden_id = contrato.crear_orden(0, 1).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert_eq!(contrato.migrar(LIMITE_PAGINA), Ok(true));
+            let productos = contrato.productos_de_vendedor(vendedor, 0, LIMITE_PAGINA).elementos;
+            assert_eq!(productos.iter().map(|p| p.id).collect::<Vec<_>>(), vec![0]);
+            let como_comprador = contrato.ordenes_como_comprador(accounts.bob, 0, LIMITE_PAGINA).elementos;
+            assert_eq!(como_comprador.iter().map(|o| o.id).collect::<Vec<_>>(), vec![orden_id]);
+            let como_vendedor = contrato.ordenes_como_vendedor(vendedor, 0, LIMITE_PAGINA).elementos;
+            assert_eq!(como_vendedor.iter().map(|o| o.id).collect::<Vec<_>>(), vec![orden_id]);
+            // Las listas anteriores se borran
+            for (indice, cuenta) in listas {
+                let anterior = ink::env::get_contract_storage::<_, Vec<u32>>(&(indice, cuenta));
+                assert_eq!(anterior, Ok(None));
+            }
+        }
 
-            // Simula que el vendedor intenta marcar la orden como recibida directamente
-            let resultado = contrato.marcar_como_recibida(orden_id);
+        // --- Pagos en tokens PSP22 ---
+        fn token() -> AccountId {
+            AccountId::from([0x70; 32])
+        }
 
-            assert!(matches!(resultado, Err(SistemaError::EstadoInvalido)));
-        }*/
+        // Función auxiliar que permite el token de prueba y publica un producto con precio en él
+        fn setup_producto_en_token() -> MarketplacePrincipal {
+            let mut contrato = setup_contract_con_vendedor();
+            contrato.permitir_token(token()).unwrap();
+            contrato
+                .publicar_producto_con_moneda(
+                    "Producto Token".to_string(),
+                    "Precio en stablecoin".to_string(),
+                    1000,
+                    10,
+                    "Test".to_string(),
+                    Moneda::Psp22(token()),
+                )
+                .unwrap();
+            contrato
+        }
 
-        // --- Errores y validaciones ---
         #[ink::test]
-        fn acceder_orden_inexistente_falla() {
+        fn permitir_y_quitar_token_ok() {
             let mut contrato = MarketplacePrincipal::new();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert!(!contrato.es_token_permitido(token()));
 
-            let resultado = contrato.obtener_orden_mut(999); // ID que no existe
+            assert!(contrato.permitir_token(token()).is_ok());
+            assert!(contrato.es_token_permitido(token()));
+            assert!(contrato.quitar_token(token()).is_ok());
+            assert!(!contrato.es_token_permitido(token()));
 
-            assert!(matches!(resultado, Err(SistemaError::OrdenNoExiste)));
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contrato.permitir_token(token()), Err(SistemaError::NoEsPropietario));
         }
 
         #[ink::test]
-        fn acceder_producto_inexistente_falla() {
-            let mut contrato = MarketplacePrincipal::new();
-
-            let resultado = contrato.obtener_producto_mut(999); // ID que no existe
-
-            assert!(matches!(resultado, Err(SistemaError::ProductosVacios)));
+        fn publicar_producto_en_token_ok() {
+            let contrato = setup_producto_en_token();
+            let producto = contrato.obtener_producto(0).unwrap();
+            assert_eq!(producto.moneda, Moneda::Psp22(token()));
+            assert_eq!(producto.precio, 1000);
         }
 
         #[ink::test]
-        fn marcar_orden_como_enviada_usuario_no_registrado_falla() {
+        fn publicar_producto_en_token_no_permitido_falla() {
             let mut contrato = setup_contract_con_vendedor();
-
-            // Publica un producto
-            let _ = contrato.publicar_producto(
-                "Producto Test".to_string(),
-                "Descripción Test".to_string(),
+            let resultado = contrato.publicar_producto_con_moneda(
+                "Producto Token".to_string(),
+                "Token desconocido".to_string(),
                 1000,
                 10,
                 "Test".to_string(),
+                Moneda::Psp22(token()),
             );
+            assert_eq!(resultado, Err(SistemaError::TokenNoPermitido));
+            assert_eq!(contrato.siguiente_id_producto, 0);
+        }
 
-            // Cambia el caller a un usuario comprador y regístralo
+        #[ink::test]
+        fn crear_orden_en_token_con_pago_nativo_falla() {
+            let mut contrato = setup_producto_en_token();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
             test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
-
-            // Crea una orden
-            let resultado = contrato.crear_orden(0, 1);
-            assert!(resultado.is_ok());
-            let orden_id = resultado.unwrap();
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
 
-            // Cambia el caller a un usuario NO registrado
-            let usuario_no_registrado = AccountId::from([0x99; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(usuario_no_registrado);
-
-            // Intenta marcar la orden como enviada
-            let resultado = contrato.marcar_orden_como_enviada(orden_id);
-            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+            pagar(2000);
+            assert_eq!(contrato.crear_orden(0, 2), Err(SistemaError::PagoIncorrecto));
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 10);
         }
 
         #[ink::test]
-        fn marcar_como_recibida_usuario_no_registrado_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn crear_orden_en_token_quitado_falla() {
+            let mut contrato = setup_producto_en_token();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            contrato.quitar_token(token()).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
 
-            // Publica un producto
-            let _ = contrato.publicar_producto(
-                "Producto Test".to_string(),
-                "Descripción Test".to_string(),
-                1000,
-                10,
-                "Test".to_string(),
-            );
+            test::set_value_transferred::<ink::env::DefaultEnvironment>(0);
+            assert_eq!(contrato.crear_orden(0, 2), Err(SistemaError::TokenNoPermitido));
+        }
 
-            // Cambia el caller a un usuario comprador y regístralo
+        #[ink::test]
+        fn orden_en_token_se_liquida_en_el_saldo_del_token() {
+            let mut contrato = setup_producto_en_token();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let vendedor = AccountId::from([0x01; 32]);
+            contrato.fijar_comision(1_000).unwrap();
             test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
 
-            // Crea una orden
-            let resultado = contrato.crear_orden(0, 1);
-            assert!(resultado.is_ok());
-            let orden_id = resultado.unwrap();
+            // El cobro con `transfer_from` requiere el token desplegado: se prueba en e2e,
+            // acá se parte de la orden ya creada
+            let mut orden = Orden::new(0, accounts.bob, vendedor, 0, 2, 2000);
+            orden.moneda = Moneda::Psp22(token());
+            let orden_id = contrato.crear_y_emitir_orden(orden).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.marcar_como_recibida(orden_id).unwrap();
 
-            // Cambia el caller a un usuario NO registrado
-            let usuario_no_registrado = AccountId::from([0x99; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(usuario_no_registrado);
+            let moneda = Moneda::Psp22(token());
+            assert_eq!(contrato.saldo_en_moneda(vendedor, moneda), 1800);
+            assert_eq!(contrato.tesoreria_en_moneda(moneda), 200);
+            // El saldo y la tesorería en token nativo no cambian
+            assert_eq!(contrato.saldo_de(vendedor), 0);
+            assert_eq!(contrato.tesoreria(), 0);
 
-            // Intenta marcar la orden como recibida
-            let resultado = contrato.marcar_como_recibida(orden_id);
-            assert!(matches!(resultado, Err(SistemaError::UsuarioNoRegistrado)));
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            assert_eq!(contrato.retirar_fondos_en_moneda(moneda, 1801), Err(SistemaError::SaldoInsuficiente));
         }
 
-        #[ink::test]
-        fn marcar_orden_como_enviada_usuario_no_es_vendedor_falla() {
+        // --- Órdenes de varios productos ---
+        // Función auxiliar con dos productos de Alice, uno de Charlie y Bob registrado como comprador
+        fn setup_carrito() -> MarketplacePrincipal {
             let mut contrato = setup_contract_con_vendedor();
-
-            // Publica un producto
-            let _ = contrato.publicar_producto(
-                "Producto Test".to_string(),
-                "Descripción Test".to_string(),
-                1000,
-                10,
-                "Test".to_string(),
-            );
-
-            // Cambia el caller a un usuario comprador y regístralo
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            contrato.publicar_producto("Celular".to_string(), "Desc".to_string(), 1000, 10, "Tecnología".to_string()).unwrap();
+            contrato.publicar_producto("Funda".to_string(), "Desc".to_string(), 100, 5, "Accesorios".to_string()).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contrato.registrar_usuario(RolUsuario::Vendedor).unwrap();
+            contrato.publicar_producto("Libro".to_string(), "Desc".to_string(), 500, 3, "Libros".to_string()).unwrap();
             test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
-
-            // Crea una orden
-            let resultado = contrato.crear_orden(0, 1);
-            assert!(resultado.is_ok());
-            let orden_id = resultado.unwrap();
+            contrato.registrar_usuario(RolUsuario::Comprador).unwrap();
+            contrato
+        }
 
-            // Cambia el caller a otro usuario registrado que NO es el vendedor
+        #[ink::test]
+        fn crear_orden_multiple_agrupa_por_vendedor() {
+            let mut contrato = setup_carrito();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
-            let _ = contrato.registrar_usuario(RolUsuario::Vendedor);
 
-            // Intenta marcar la orden como enviada (no debería poder porque no es el vendedor de esta orden)
-            let resultado = contrato.marcar_orden_como_enviada(orden_id);
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            pagar(3100);
+            let ids = contrato.crear_orden_multiple(vec![(0, 1), (2, 2), (1, 1), (0, 1)]).unwrap();
+            assert_eq!(ids, vec![0, 1]);
+
+            // Las líneas repetidas del celular se suman en la orden de Alice
+            let de_alice = contrato.obtener_orden(0).unwrap();
+            assert_eq!(de_alice.vendedor, accounts.alice);
+            assert_eq!(
+                de_alice.lineas,
+                vec![
+                    LineaOrden { producto_id: 0, cantidad: 2, monto: 2000 },
+                    LineaOrden { producto_id: 1, cantidad: 1, monto: 100 },
+                ]
+            );
+            assert_eq!(de_alice.monto, 2100);
+            let de_charlie = contrato.obtener_orden(1).unwrap();
+            assert_eq!(de_charlie.vendedor, accounts.charlie);
+            assert_eq!(de_charlie.lineas, vec![LineaOrden { producto_id: 2, cantidad: 2, monto: 1000 }]);
+            assert_eq!(de_charlie.monto, 1000);
+
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 8);
+            assert_eq!(contrato.obtener_producto(1).unwrap().cantidad, 4);
+            assert_eq!(contrato.obtener_producto(2).unwrap().cantidad, 1);
+            assert_eq!(contrato.ordenes_como_comprador(accounts.bob, 0, LIMITE_PAGINA).elementos.len(), 2);
         }
 
         #[ink::test]
-        fn marcar_como_recibida_usuario_no_es_comprador_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn crear_orden_multiple_sin_stock_no_modifica_nada() {
+            let mut contrato = setup_carrito();
+
+            // La segunda línea supera el stock del libro; la primera sería válida
+            pagar(3000);
+            assert_eq!(contrato.crear_orden_multiple(vec![(0, 1), (2, 4)]), Err(SistemaError::StockInsuficiente));
+            // Sumadas, las líneas repetidas también superan el stock
+            pagar(2000);
+            assert_eq!(contrato.crear_orden_multiple(vec![(2, 2), (2, 2)]), Err(SistemaError::StockInsuficiente));
+
+            assert_eq!(contrato.siguiente_id_orden, 0);
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 10);
+            assert_eq!(contrato.obtener_producto(2).unwrap().cantidad, 3);
+        }
 
-            // Publica un producto
-            let _ = contrato.publicar_producto(
-                "Producto Test".to_string(),
-                "Descripción Test".to_string(),
-                1000,
-                10,
-                "Test".to_string(),
+        #[ink::test]
+        fn crear_orden_multiple_valida_carrito_y_pago() {
+            let mut contrato = setup_carrito();
+
+            test::set_value_transferred::<ink::env::DefaultEnvironment>(0);
+            assert_eq!(contrato.crear_orden_multiple(Vec::new()), Err(SistemaError::CarritoInvalido));
+            let demasiadas = vec![(0, 1); LIMITE_CARRITO as usize + 1];
+            assert_eq!(contrato.crear_orden_multiple(demasiadas), Err(SistemaError::CarritoInvalido));
+            assert_eq!(contrato.crear_orden_multiple(vec![(0, 1), (1, 0)]), Err(SistemaError::CantidadInsuficiente));
+            assert_eq!(contrato.crear_orden_multiple(vec![(0, 1), (9, 1)]), Err(SistemaError::ProductosVacios));
+            // Las líneas repetidas que desbordan la cantidad no se reportan como falta de stock
+            assert_eq!(
+                contrato.crear_orden_multiple(vec![(0, u32::MAX), (0, 1)]),
+                Err(SistemaError::CantidadExcedida)
             );
 
-            // Cambia el caller a un usuario comprador y regístralo
-            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
-
-            // Crea una orden
-            let resultado = contrato.crear_orden(0, 1);
-            assert!(resultado.is_ok());
-            let orden_id = resultado.unwrap();
-
-            // Cambia el caller de vuelta al vendedor para marcar como enviada
-            let vendedor = AccountId::from([0x01; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
-
-            // Marca la orden como enviada
-            let resultado = contrato.marcar_orden_como_enviada(orden_id);
-            assert!(resultado.is_ok());
+            // El pago tiene que cubrir exactamente todas las líneas
+            pagar(1000);
+            assert_eq!(contrato.crear_orden_multiple(vec![(0, 1), (1, 1)]), Err(SistemaError::PagoIncorrecto));
+            assert_eq!(contrato.siguiente_id_orden, 0);
+        }
 
-            // Cambia el caller a otro usuario registrado que NO es el comprador
+        #[ink::test]
+        fn crear_orden_multiple_con_producto_propio_falla() {
+            let mut contrato = setup_carrito();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
             test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            contrato.modificar_rol_usuario(RolUsuario::Ambos).unwrap();
 
-            // Intenta marcar la orden como recibida (no debería poder porque no es el comprador de esta orden)
-            let resultado = contrato.marcar_como_recibida(orden_id);
-            assert!(matches!(resultado, Err(SistemaError::NoEsRolCorrecto)));
+            pagar(2000);
+            assert_eq!(
+                contrato.crear_orden_multiple(vec![(0, 1), (2, 2)]),
+                Err(SistemaError::AutoCompraNoPermitida)
+            );
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 10);
         }
 
         #[ink::test]
-        fn marcar_orden_como_enviada_orden_inexistente_falla() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn orden_de_varios_productos_se_cancela_y_recibe_por_linea() {
+            let mut contrato = setup_carrito();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            pagar(2100);
+            let ids = contrato.crear_orden_multiple(vec![(0, 2), (1, 1)]).unwrap();
+            pagar(2100);
+            let segunda = contrato.crear_orden_multiple(vec![(0, 2), (1, 1)]).unwrap();
 
-            // Intenta marcar una orden inexistente como enviada
-            let resultado = contrato.marcar_orden_como_enviada(999);
-            assert!(matches!(resultado, Err(SistemaError::OrdenNoExiste)));
+            // La cancelación devuelve el stock de todas las líneas
+            contrato.solicitar_cancelacion(ids[0]).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            contrato.aceptar_cancelacion(ids[0]).unwrap();
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 8);
+            assert_eq!(contrato.obtener_producto(1).unwrap().cantidad, 4);
+
+            // Al recibirla, cada producto suma sus unidades vendidas
+            contrato.marcar_orden_como_enviada(segunda[0]).unwrap();
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contrato.marcar_como_recibida(segunda[0]).unwrap();
+            assert_eq!(contrato.obtener_producto(0).unwrap().unidades_vendidas, 2);
+            assert_eq!(contrato.obtener_producto(1).unwrap().unidades_vendidas, 1);
+            assert_eq!(contrato.saldo_de(accounts.alice), 2100);
         }
 
+        // --- Plazos de las órdenes ---
         #[ink::test]
-        fn marcar_como_recibida_orden_inexistente_falla() {
-            let mut contrato = setup_contract_con_vendedor();
-
-            // Intenta marcar una orden inexistente como recibida
-            let resultado = contrato.marcar_como_recibida(999);
-            assert!(matches!(resultado, Err(SistemaError::OrdenNoExiste)));
+        fn orden_registra_creacion_y_envio() {
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let orden = contrato.obtener_orden(orden_id).unwrap();
+            assert_eq!(orden.creada_en, 1_000);
+            assert_eq!(orden.enviada_en, None);
+
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(4_000);
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
+            assert_eq!(contrato.obtener_orden(orden_id).unwrap().enviada_en, Some(4_000));
         }
 
         #[ink::test]
-        fn marcar_como_recibida_ok() {
-            let mut contrato = setup_contract_con_vendedor();
+        fn finalizar_por_timeout_cancela_orden_no_enviada() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let saldo_comprador = balance_de(accounts.bob);
+
+            // Cualquier cuenta puede cerrar la orden, aunque no esté registrada
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(PLAZO_ENVIO - 1);
+            assert_eq!(contrato.finalizar_por_timeout(orden_id), Err(SistemaError::PlazoNoVencido));
+
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(PLAZO_ENVIO);
+            assert!(contrato.finalizar_por_timeout(orden_id).is_ok());
+            assert_eq!(contrato.obtener_orden(orden_id).unwrap().estado, EstadoOrden::Cancelada);
+            assert_eq!(contrato.obtener_producto(0).unwrap().cantidad, 10);
+            // El reembolso queda en el saldo del comprador hasta que lo retire
+            assert_eq!(contrato.saldo_de(accounts.bob), 2000);
+            assert_eq!(balance_de(accounts.bob), saldo_comprador);
+            assert_eq!(contrato.finalizar_por_timeout(orden_id), Err(SistemaError::EstadoInvalido));
 
-            // Publica un producto
-            let _ = contrato.publicar_producto(
-                "Producto Test".to_string(),
-                "Descripción Test".to_string(),
-                1000,
-                10,
-                "Test".to_string(),
-            );
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(contrato.retirar_fondos(2000).is_ok());
+            assert_eq!(contrato.saldo_de(accounts.bob), 0);
+            assert_eq!(balance_de(accounts.bob), saldo_comprador + 2000);
+        }
 
-            // Cambia el caller a un usuario comprador y regístralo
+        #[ink::test]
+        fn finalizar_por_timeout_no_cobra_comision_sobre_el_reembolso() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            contrato.fijar_comision(500).unwrap();
 
-            // Crea una orden
-            let resultado = contrato.crear_orden(0, 1);
-            assert!(resultado.is_ok());
-            let orden_id = resultado.unwrap();
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(PLAZO_ENVIO);
+            contrato.finalizar_por_timeout(orden_id).unwrap();
+            assert_eq!(contrato.saldo_de(accounts.bob), 2000);
+            assert_eq!(contrato.tesoreria(), 0);
+        }
 
-            // Cambia el caller de vuelta al vendedor para marcar como enviada
+        #[ink::test]
+        fn finalizar_por_timeout_recibe_orden_enviada() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
             let vendedor = AccountId::from([0x01; 32]);
-            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
-
-            // Marca la orden como enviada
-            let resultado = contrato.marcar_orden_como_enviada(orden_id);
-            assert!(resultado.is_ok());
 
-            // Cambia el caller de vuelta al comprador para marcar como recibida
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            // El plazo de envío ya venció, pero el vendedor envió antes de que alguien cerrara la orden
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(PLAZO_ENVIO + 1);
+            test::set_caller::<ink::env::DefaultEnvironment>(vendedor);
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
 
-            // Marca la orden como recibida (debe ser exitoso)
-            let resultado = contrato.marcar_como_recibida(orden_id);
-            assert!(resultado.is_ok());
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(PLAZO_ENVIO + PLAZO_CONFIRMACION);
+            assert_eq!(contrato.finalizar_por_timeout(orden_id), Err(SistemaError::PlazoNoVencido));
 
-            // Verifica que el estado cambió a Recibida
-            let orden = &contrato.ordenes[orden_id as usize];
-            assert_eq!(orden.estado, EstadoOrden::Recibida);
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(PLAZO_ENVIO + 1 + PLAZO_CONFIRMACION);
+            assert!(contrato.finalizar_por_timeout(orden_id).is_ok());
+            assert_eq!(contrato.obtener_orden(orden_id).unwrap().estado, EstadoOrden::Recibida);
+            assert_eq!(contrato.saldo_de(vendedor), 2000);
+            assert_eq!(contrato.obtener_producto(0).unwrap().unidades_vendidas, 2);
         }
 
         #[ink::test]
-        fn marcar_como_recibida_estado_pendiente_falla() {
-            let mut contrato = setup_contract_con_vendedor();
-
-            // Publica un producto
-            let _ = contrato.publicar_producto(
-                "Producto Test".to_string(),
-                "Descripción Test".to_string(),
-                1000,
-                10,
-                "Test".to_string(),
-            );
-
-            // Cambia el caller a un usuario comprador y regístralo
+        fn finalizar_por_timeout_no_aplica_a_disputas() {
+            let (mut contrato, orden_id) = setup_orden_pendiente();
             let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            contrato.marcar_orden_como_enviada(orden_id).unwrap();
             test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let _ = contrato.registrar_usuario(RolUsuario::Comprador);
-
-            // Crea una orden (estado inicial: Pendiente)
-            let resultado = contrato.crear_orden(0, 1);
-            assert!(resultado.is_ok());
-            let orden_id = resultado.unwrap();
+            contrato.abrir_disputa(orden_id, Hash::from([0x07; 32])).unwrap();
 
-            // Verifica que la orden está en estado Pendiente
-            let orden = &contrato.ordenes[orden_id as usize];
-            assert_eq!(orden.estado, EstadoOrden::Pendiente);
+            test::set_block_timestamp::<ink::env::DefaultEnvironment>(PLAZO_ENVIO + PLAZO_CONFIRMACION);
+            assert_eq!(contrato.finalizar_por_timeout(orden_id), Err(SistemaError::EstadoInvalido));
+            assert_eq!(contrato.finalizar_por_timeout(orden_id + 1), Err(SistemaError::OrdenNoExiste));
 
-            // Intenta marcar la orden como recibida directamente desde Pendiente (debe fallar)
-            let resultado = contrato.marcar_como_recibida(orden_id);
-            assert!(matches!(resultado, Err(SistemaError::EstadoInvalido)));
+            test::set_caller::<ink::env::DefaultEnvironment>(AccountId::from([0x01; 32]));
+            contrato.pausar().unwrap();
+            assert_eq!(contrato.finalizar_por_timeout(orden_id), Err(SistemaError::ContratoPausado));
         }
     } // <-- cierre del mod tests
 } // <-- cierre del mod marketplace_principal
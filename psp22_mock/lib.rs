@@ -0,0 +1,404 @@
+//! Token PSP22 mínimo para probar los pagos en tokens de `MarketplacePrincipal`
+//! sin salir de un nodo local.
+//!
+//! Implementa el trait `PSP22` que declara el marketplace, así que comparte sus
+//! selectores. No tiene metadatos ni emisión posterior al despliegue: todo el
+//! suministro queda en la cuenta que lo instancia.
+
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+// El código que genera `#[ink::contract]` dispara este lint en el nightly fijado.
+#![allow(non_local_definitions)]
+
+#[ink::contract]
+mod psp22_mock {
+    use ink::prelude::vec::Vec;
+    use ink::storage::Mapping;
+    use marketplace_principal::{PSP22Error, PSP22};
+
+    /// Token fungible con saldos y autorizaciones.
+    #[ink(storage)]
+    pub struct Psp22Mock {
+        /// Suministro total emitido al desplegar.
+        suministro: Balance,
+        /// Saldo de cada cuenta.
+        saldos: Mapping<AccountId, Balance>,
+        /// Monto que un gastador puede mover en nombre de un dueño, por `(dueño, gastador)`.
+        autorizaciones: Mapping<(AccountId, AccountId), Balance>,
+    }
+
+    impl Psp22Mock {
+        /// Emite `suministro` tokens a la cuenta que despliega el contrato.
+        #[ink(constructor)]
+        pub fn new(suministro: Balance) -> Self {
+            let mut saldos = Mapping::default();
+            saldos.insert(Self::env().caller(), &suministro);
+            Self {
+                suministro,
+                saldos,
+                autorizaciones: Mapping::default(),
+            }
+        }
+
+        /// Mueve `monto` tokens de `desde` a `hacia`.
+        ///
+        /// # Errores
+        ///
+        /// - `InsufficientBalance` si `desde` no tiene saldo suficiente.
+        fn transferir(&mut self, desde: AccountId, hacia: AccountId, monto: Balance) -> Result<(), PSP22Error> {
+            let saldo_desde = self.saldos.get(desde).unwrap_or(0);
+            if saldo_desde < monto {
+                return Err(PSP22Error::InsufficientBalance);
+            }
+            self.saldos.insert(desde, &(saldo_desde - monto));
+            let saldo_hacia = self.saldos.get(hacia).unwrap_or(0);
+            self.saldos.insert(hacia, &saldo_hacia.saturating_add(monto));
+            Ok(())
+        }
+    }
+
+    impl PSP22 for Psp22Mock {
+        #[ink(message)]
+        fn total_supply(&self) -> u128 {
+            self.suministro
+        }
+
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> u128 {
+            self.saldos.get(owner).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        fn allowance(&self, owner: AccountId, spender: AccountId) -> u128 {
+            self.autorizaciones.get((owner, spender)).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        fn transfer(&mut self, to: AccountId, value: u128, _data: Vec<u8>) -> Result<(), PSP22Error> {
+            let caller = self.env().caller();
+            self.transferir(caller, to, value)
+        }
+
+        #[ink(message)]
+        fn transfer_from(&mut self, from: AccountId, to: AccountId, value: u128, _data: Vec<u8>) -> Result<(), PSP22Error> {
+            let caller = self.env().caller();
+            let autorizado = self.allowance(from, caller);
+            if autorizado < value {
+                return Err(PSP22Error::InsufficientAllowance);
+            }
+            self.transferir(from, to, value)?;
+            self.autorizaciones.insert((from, caller), &(autorizado - value));
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn approve(&mut self, spender: AccountId, value: u128) -> Result<(), PSP22Error> {
+            let caller = self.env().caller();
+            self.autorizaciones.insert((caller, spender), &value);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn increase_allowance(&mut self, spender: AccountId, delta_value: u128) -> Result<(), PSP22Error> {
+            let caller = self.env().caller();
+            let autorizado = self.allowance(caller, spender).saturating_add(delta_value);
+            self.autorizaciones.insert((caller, spender), &autorizado);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn decrease_allowance(&mut self, spender: AccountId, delta_value: u128) -> Result<(), PSP22Error> {
+            let caller = self.env().caller();
+            let autorizado = self.allowance(caller, spender);
+            if autorizado < delta_value {
+                return Err(PSP22Error::InsufficientAllowance);
+            }
+            self.autorizaciones.insert((caller, spender), &(autorizado - delta_value));
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test;
+
+        #[ink::test]
+        fn new_emite_el_suministro_al_caller() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let token = Psp22Mock::new(1_000);
+            assert_eq!(token.total_supply(), 1_000);
+            assert_eq!(token.balance_of(accounts.alice), 1_000);
+            assert_eq!(token.balance_of(accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn transfer_sin_saldo_falla() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut token = Psp22Mock::new(1_000);
+            assert_eq!(token.transfer(accounts.bob, 400, Vec::new()), Ok(()));
+            assert_eq!(token.balance_of(accounts.bob), 400);
+            assert_eq!(token.transfer(accounts.bob, 601, Vec::new()), Err(PSP22Error::InsufficientBalance));
+            assert_eq!(token.balance_of(accounts.alice), 600);
+        }
+
+        #[ink::test]
+        fn transfer_from_consume_la_autorizacion() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut token = Psp22Mock::new(1_000);
+            token.approve(accounts.bob, 300).unwrap();
+
+            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(token.transfer_from(accounts.alice, accounts.charlie, 200, Vec::new()), Ok(()));
+            assert_eq!(token.balance_of(accounts.charlie), 200);
+            assert_eq!(token.allowance(accounts.alice, accounts.bob), 100);
+            assert_eq!(
+                token.transfer_from(accounts.alice, accounts.charlie, 101, Vec::new()),
+                Err(PSP22Error::InsufficientAllowance)
+            );
+        }
+    }
+
+    /// Tests de integración contra un nodo con `pallet-contracts`.
+    ///
+    /// Para correrlos, con `substrate-contracts-node` instalado en el `PATH` (o su
+    /// ruta en la variable `CONTRACTS_NODE`), desde la raíz del workspace:
+    ///
+    /// ```sh
+    /// cargo test -p psp22_mock --features e2e-tests
+    /// ```
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::ContractsBackend;
+        use marketplace_principal::{
+            MarketplacePrincipal, MarketplacePrincipalRef, Moneda, RolUsuario, SistemaError, VERSION_ALMACENAMIENTO,
+        };
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+        type Cliente = ink_e2e::Client<ink_e2e::PolkadotConfig, ink::env::DefaultEnvironment>;
+
+        // Suministro del token, todo en la cuenta de Bob
+        const SUMINISTRO: Balance = 1_000_000;
+
+        // Despliega el token y un marketplace que lo permite, con un producto de Alice
+        // en el token (1000 por unidad, stock 5) y Bob registrado como comprador
+        async fn desplegar(client: &mut Cliente) -> E2EResult<(AccountId, AccountId)> {
+            let mut constructor = Psp22MockRef::new(SUMINISTRO);
+            let token = client
+                .instantiate("psp22_mock", &ink_e2e::bob(), &mut constructor)
+                .submit()
+                .await
+                .expect("instantiate psp22_mock failed");
+
+            let mut constructor = MarketplacePrincipalRef::new();
+            let marketplace = client
+                .instantiate("marketplace_principal", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("instantiate marketplace failed");
+            let mut marketplace_call = marketplace.call_builder::<MarketplacePrincipal>();
+
+            let permitir = marketplace_call.permitir_token(token.account_id);
+            client.call(&ink_e2e::alice(), &permitir).submit().await.expect("permitir_token failed");
+            let registrar = marketplace_call.registrar_usuario(RolUsuario::Vendedor);
+            client.call(&ink_e2e::alice(), &registrar).submit().await.expect("registrar vendedor failed");
+            let publicar = marketplace_call.publicar_producto_con_moneda(
+                "Celular".into(),
+                "Un buen celular".into(),
+                1000,
+                5,
+                "Tecnología".into(),
+                Moneda::Psp22(token.account_id),
+            );
+            client.call(&ink_e2e::alice(), &publicar).submit().await.expect("publicar failed");
+            let registrar = marketplace_call.registrar_usuario(RolUsuario::Comprador);
+            client.call(&ink_e2e::bob(), &registrar).submit().await.expect("registrar comprador failed");
+
+            Ok((token.account_id, marketplace.account_id))
+        }
+
+        // Saldo de una cuenta en el token
+        async fn saldo_token(client: &mut Cliente, token: AccountId, cuenta: AccountId) -> E2EResult<Balance> {
+            let token_call = ink_e2e::create_call_builder::<Psp22Mock>(token);
+            let consulta = token_call.balance_of(cuenta);
+            Ok(client.call(&ink_e2e::alice(), &consulta).dry_run().await?.return_value())
+        }
+
+        // Verifica que el token, el stock y los saldos del marketplace siguen como los dejó `desplegar`
+        async fn verificar_sin_cambios(client: &mut Cliente, token: AccountId, marketplace: AccountId) -> E2EResult<()> {
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            assert_eq!(saldo_token(client, token, bob).await?, SUMINISTRO);
+            assert_eq!(saldo_token(client, token, marketplace).await?, 0);
+
+            let marketplace_call = ink_e2e::create_call_builder::<MarketplacePrincipal>(marketplace);
+            let consulta = marketplace_call.obtener_producto(0);
+            let producto = client.call(&ink_e2e::alice(), &consulta).dry_run().await?.return_value();
+            assert_eq!(producto.map(|p| p.cantidad), Some(5));
+            for cuenta in [alice, bob] {
+                let consulta = marketplace_call.saldo_en_moneda(cuenta, Moneda::Psp22(token));
+                let resultado = client.call(&ink_e2e::alice(), &consulta).dry_run().await?;
+                assert_eq!(resultado.return_value(), 0);
+            }
+            let consulta = marketplace_call.tesoreria_en_moneda(Moneda::Psp22(token));
+            let resultado = client.call(&ink_e2e::alice(), &consulta).dry_run().await?;
+            assert_eq!(resultado.return_value(), 0);
+            Ok(())
+        }
+
+        /// Compra un producto con precio en el token y retira lo cobrado.
+        #[ink_e2e::test]
+        async fn orden_en_token_cobra_y_liquida(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            // Given
+            let (token, marketplace) = desplegar(&mut client).await?;
+            let mut token_call = ink_e2e::create_call_builder::<Psp22Mock>(token);
+            let mut marketplace_call = ink_e2e::create_call_builder::<MarketplacePrincipal>(marketplace);
+            let moneda = Moneda::Psp22(token);
+
+            // When
+            let aprobar = token_call.approve(marketplace, 2000);
+            client.call(&ink_e2e::bob(), &aprobar).submit().await.expect("approve failed");
+            let comprar = marketplace_call.crear_orden(0, 2);
+            client.call(&ink_e2e::bob(), &comprar).submit().await.expect("crear_orden failed");
+
+            // Then: el pago queda retenido en el marketplace
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            assert_eq!(saldo_token(&mut client, token, marketplace).await?, 2000);
+            assert_eq!(saldo_token(&mut client, token, bob).await?, 998_000);
+
+            // When: se envía, se recibe y la vendedora retira
+            let enviar = marketplace_call.marcar_orden_como_enviada(0);
+            client.call(&ink_e2e::alice(), &enviar).submit().await.expect("enviar failed");
+            let recibir = marketplace_call.marcar_como_recibida(0);
+            client.call(&ink_e2e::bob(), &recibir).submit().await.expect("recibir failed");
+            let retirar = marketplace_call.retirar_fondos_en_moneda(moneda, 2000);
+            client.call(&ink_e2e::alice(), &retirar).submit().await.expect("retirar failed");
+
+            // Then
+            assert_eq!(saldo_token(&mut client, token, alice).await?, 2000);
+            let consulta = marketplace_call.saldo_en_moneda(alice, moneda);
+            let resultado = client.call(&ink_e2e::alice(), &consulta).dry_run().await?;
+            assert_eq!(resultado.return_value(), 0);
+
+            Ok(())
+        }
+
+        /// Sin `approve` previo, `transfer_from` falla y la orden no se crea.
+        #[ink_e2e::test]
+        async fn orden_en_token_sin_autorizacion_falla(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            // Given
+            let (token, marketplace) = desplegar(&mut client).await?;
+            let mut marketplace_call = ink_e2e::create_call_builder::<MarketplacePrincipal>(marketplace);
+
+            // When
+            let comprar = marketplace_call.crear_orden(0, 2);
+            let resultado = client.call(&ink_e2e::bob(), &comprar).dry_run().await?;
+            assert_eq!(resultado.return_value(), Err(SistemaError::TransferenciaFallida));
+            assert!(client.call(&ink_e2e::bob(), &comprar).submit().await.is_err());
+
+            // Then
+            verificar_sin_cambios(&mut client, token, marketplace).await
+        }
+
+        /// Un token quitado de la lista de permitidos deja de aceptar órdenes, aunque
+        /// el comprador haya autorizado al marketplace.
+        #[ink_e2e::test]
+        async fn orden_en_token_no_permitido_falla(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            // Given
+            let (token, marketplace) = desplegar(&mut client).await?;
+            let mut token_call = ink_e2e::create_call_builder::<Psp22Mock>(token);
+            let mut marketplace_call = ink_e2e::create_call_builder::<MarketplacePrincipal>(marketplace);
+            let quitar = marketplace_call.quitar_token(token);
+            client.call(&ink_e2e::alice(), &quitar).submit().await.expect("quitar_token failed");
+            let aprobar = token_call.approve(marketplace, 2000);
+            client.call(&ink_e2e::bob(), &aprobar).submit().await.expect("approve failed");
+
+            // When
+            let comprar = marketplace_call.crear_orden(0, 2);
+            let resultado = client.call(&ink_e2e::bob(), &comprar).dry_run().await?;
+            assert_eq!(resultado.return_value(), Err(SistemaError::TokenNoPermitido));
+            assert!(client.call(&ink_e2e::bob(), &comprar).submit().await.is_err());
+
+            // Then: la autorización sigue sin usar
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let consulta = token_call.allowance(bob, marketplace);
+            let resultado = client.call(&ink_e2e::bob(), &consulta).dry_run().await?;
+            assert_eq!(resultado.return_value(), 2000);
+            verificar_sin_cambios(&mut client, token, marketplace).await
+        }
+
+        /// Al cancelar de común acuerdo, los tokens se acreditan al comprador, que los
+        /// retira, y el stock vuelve al producto.
+        #[ink_e2e::test]
+        async fn cancelacion_en_token_reembolsa_al_comprador(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            // Given
+            let (token, marketplace) = desplegar(&mut client).await?;
+            let mut token_call = ink_e2e::create_call_builder::<Psp22Mock>(token);
+            let mut marketplace_call = ink_e2e::create_call_builder::<MarketplacePrincipal>(marketplace);
+            let aprobar = token_call.approve(marketplace, 2000);
+            client.call(&ink_e2e::bob(), &aprobar).submit().await.expect("approve failed");
+            let comprar = marketplace_call.crear_orden(0, 2);
+            client.call(&ink_e2e::bob(), &comprar).submit().await.expect("crear_orden failed");
+            assert_eq!(saldo_token(&mut client, token, marketplace).await?, 2000);
+
+            // When
+            let solicitar = marketplace_call.solicitar_cancelacion(0);
+            client.call(&ink_e2e::bob(), &solicitar).submit().await.expect("solicitar_cancelacion failed");
+            let aceptar = marketplace_call.aceptar_cancelacion(0);
+            client.call(&ink_e2e::alice(), &aceptar).submit().await.expect("aceptar_cancelacion failed");
+
+            // Then: el reembolso queda acreditado hasta que el comprador lo retira
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let consulta = marketplace_call.saldo_en_moneda(bob, Moneda::Psp22(token));
+            let resultado = client.call(&ink_e2e::bob(), &consulta).dry_run().await?;
+            assert_eq!(resultado.return_value(), 2000);
+            let retirar = marketplace_call.retirar_fondos_en_moneda(Moneda::Psp22(token), 2000);
+            client.call(&ink_e2e::bob(), &retirar).submit().await.expect("retirar failed");
+            verificar_sin_cambios(&mut client, token, marketplace).await
+        }
+
+        /// El propietario reemplaza el código del marketplace y los registros se conservan.
+        #[ink_e2e::test]
+        async fn actualizar_codigo_conserva_el_estado(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            // Given
+            let (token, marketplace) = desplegar(&mut client).await?;
+            let mut marketplace_call = ink_e2e::create_call_builder::<MarketplacePrincipal>(marketplace);
+            // El código ya está en la cadena: `upload` devuelve su hash sin volver a guardarlo
+            let subido = client
+                .upload("marketplace_principal", &ink_e2e::alice())
+                .submit()
+                .await
+                .expect("upload failed");
+
+            // When
+            let actualizar = marketplace_call.actualizar_codigo(subido.code_hash);
+            let resultado = client.call(&ink_e2e::alice(), &actualizar).dry_run().await?;
+            assert_eq!(resultado.return_value(), Ok(()));
+            client.call(&ink_e2e::alice(), &actualizar).submit().await.expect("actualizar_codigo failed");
+
+            // Then: no hay migración pendiente y el producto sigue publicado
+            let consulta = marketplace_call.version_almacenamiento();
+            let resultado = client.call(&ink_e2e::alice(), &consulta).dry_run().await?;
+            assert_eq!(resultado.return_value(), VERSION_ALMACENAMIENTO);
+            verificar_sin_cambios(&mut client, token, marketplace).await
+        }
+
+        /// Sin código subido con ese hash, `set_code_hash` falla y el contrato sigue igual.
+        #[ink_e2e::test]
+        async fn actualizar_codigo_con_hash_desconocido_falla(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            // Given
+            let (token, marketplace) = desplegar(&mut client).await?;
+            let mut marketplace_call = ink_e2e::create_call_builder::<MarketplacePrincipal>(marketplace);
+
+            // When
+            let actualizar = marketplace_call.actualizar_codigo(Hash::from([0x42; 32]));
+            let resultado = client.call(&ink_e2e::alice(), &actualizar).dry_run().await?;
+            assert_eq!(resultado.return_value(), Err(SistemaError::CodigoInvalido));
+            assert!(client.call(&ink_e2e::alice(), &actualizar).submit().await.is_err());
+
+            // Then
+            verificar_sin_cambios(&mut client, token, marketplace).await
+        }
+    }
+}
@@ -1,139 +1,794 @@
+//! Contrato de reportes de solo lectura sobre `MarketplacePrincipal`.
+//!
+//! `ReportesView` accede al marketplace a través del trait `MarketplaceConsultas`,
+//! que solo declara consultas `&self`. Las consultas compilan:
+//!
+//! ```no_run
+//! use marketplace_principal::MarketplaceConsultas;
+//!
+//! type Marketplace = ink::contract_ref!(MarketplaceConsultas, ink::env::DefaultEnvironment);
+//! let marketplace: Marketplace = ink::primitives::AccountId::from([0x01; 32]).into();
+//! let _ = marketplace.listar_productos(0, 10);
+//! let _ = marketplace.ordenes_como_comprador(ink::primitives::AccountId::from([0x02; 32]), 0, 10);
+//! ```
+//!
+//! pero no es posible crear órdenes:
+//!
+//! ```compile_fail
+//! use marketplace_principal::MarketplaceConsultas;
+//!
+//! type Marketplace = ink::contract_ref!(MarketplaceConsultas, ink::env::DefaultEnvironment);
+//! let mut marketplace: Marketplace = ink::primitives::AccountId::from([0x01; 32]).into();
+//! let _ = marketplace.crear_orden(0, 1);
+//! ```
+//!
+//! ni publicar productos:
+//!
+//! ```compile_fail
+//! use marketplace_principal::MarketplaceConsultas;
+//!
+//! type Marketplace = ink::contract_ref!(MarketplaceConsultas, ink::env::DefaultEnvironment);
+//! let mut marketplace: Marketplace = ink::primitives::AccountId::from([0x01; 32]).into();
+//! let _ = marketplace.publicar_producto("Celular".into(), "Desc".into(), 1000, 5, "Tecnología".into());
+//! ```
+//!
+//! ni calificar:
+//!
+//! ```compile_fail
+//! use marketplace_principal::MarketplaceConsultas;
+//!
+//! type Marketplace = ink::contract_ref!(MarketplaceConsultas, ink::env::DefaultEnvironment);
+//! let mut marketplace: Marketplace = ink::primitives::AccountId::from([0x01; 32]).into();
+//! let _ = marketplace.calificar_vendedor(0, 5);
+//! let _ = marketplace.calificar_comprador(0, 5);
+//! ```
+
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
+// El código que genera `#[ink::contract]` dispara este lint en el nightly fijado.
+#![allow(non_local_definitions)]
 
 #[ink::contract]
 mod reportes_view {
+    use ink::prelude::collections::BTreeMap;
+    use ink::prelude::string::String;
+    use ink::prelude::vec::Vec;
+    use marketplace_principal::{
+        EstadoOrden, MarketplaceConsultas, Moneda, Orden, Pagina, Producto, Reputacion, ResolucionDisputa,
+        Usuario, ESCALA_PROMEDIO, LIMITE_PAGINA,
+    };
+
+    /// Calificaciones mínimas que necesita un usuario para aparecer en un ranking.
+    pub const MINIMO_CALIFICACIONES_RANKING: u32 = 3;
+
+    /// Posición de un usuario en un ranking de reputación.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct PosicionRanking {
+        /// Cuenta del usuario.
+        pub cuenta: AccountId,
+        /// Promedio de calificaciones, escalado como en `MarketplacePrincipal`.
+        pub promedio: u32,
+        /// Cantidad de calificaciones recibidas en el rol rankeado.
+        pub calificaciones: u32,
+    }
+
+    /// Estadísticas de ventas de una categoría de productos.
+    ///
+    /// Solo se consideran las órdenes que llegaron a `Recibida`.
+    #[derive(Debug, Clone, PartialEq, Eq, Default)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct EstadisticasCategoria {
+        /// Nombre de la categoría.
+        pub categoria: String,
+        /// Cantidad de órdenes concretadas.
+        pub total_ordenes: u32,
+        /// Unidades vendidas.
+        pub unidades_vendidas: u32,
+        /// Monto bruto pagado en token nativo por las órdenes.
+        pub volumen: Balance,
+        /// Promedio de los puntajes dados al vendedor, escalado por `ESCALA_PROMEDIO`.
+        pub promedio_calificacion_vendedor: u32,
+        /// Cantidad de órdenes en las que el comprador calificó al vendedor.
+        pub calificaciones: u32,
+    }
+
+    /// Cantidad de órdenes en cada estado.
+    #[derive(Debug, Clone, PartialEq, Eq, Default)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct ConteoPorEstado {
+        /// Órdenes pendientes de envío.
+        pub pendientes: u32,
+        /// Órdenes enviadas y todavía no recibidas.
+        pub enviadas: u32,
+        /// Órdenes recibidas.
+        pub recibidas: u32,
+        /// Órdenes canceladas.
+        pub canceladas: u32,
+        /// Órdenes con una disputa abierta.
+        pub en_disputa: u32,
+    }
+
+    impl ConteoPorEstado {
+        /// Suma una orden en el casillero de su estado.
+        fn contar(&mut self, estado: &EstadoOrden) {
+            let casillero = match estado {
+                EstadoOrden::Pendiente => &mut self.pendientes,
+                EstadoOrden::Enviada => &mut self.enviadas,
+                EstadoOrden::Recibida => &mut self.recibidas,
+                EstadoOrden::Cancelada => &mut self.canceladas,
+                EstadoOrden::EnDisputa => &mut self.en_disputa,
+            };
+            *casillero = casillero.saturating_add(1);
+        }
+    }
 
-    /// Defines the storage of your contract.
-    /// Add new fields to the below struct in order
-    /// to add new static storage fields to your contract.
+    /// Resumen de la actividad de una cuenta en el marketplace.
+    #[derive(Debug, Clone, PartialEq, Eq, Default)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct ResumenOrdenesUsuario {
+        /// Órdenes en las que la cuenta compró, por estado.
+        pub como_comprador: ConteoPorEstado,
+        /// Órdenes en las que la cuenta vendió, por estado.
+        pub como_vendedor: ConteoPorEstado,
+        /// Total efectivamente pagado en token nativo como comprador.
+        pub total_gastado: Balance,
+        /// Total efectivamente cobrado en token nativo como vendedor, sin la comisión
+        /// que retuvo el marketplace.
+        pub total_ganado: Balance,
+    }
+
+    /// Contrato de solo lectura que genera reportes a partir de los datos
+    /// públicos de `MarketplacePrincipal`.
     #[ink(storage)]
     pub struct ReportesView {
-        /// Stores a single `bool` value on the storage.
-        value: bool,
+        /// Referencia al marketplace del que se leen los datos. Solo expone las
+        /// consultas de `MarketplaceConsultas`, nunca mensajes que modifiquen estado.
+        marketplace: ink::contract_ref!(MarketplaceConsultas),
     }
 
     impl ReportesView {
-        /// Constructor that initializes the `bool` value to the given `init_value`.
+        /// Crea el contrato de reportes apuntando a una instancia ya desplegada
+        /// de `MarketplacePrincipal`.
         #[ink(constructor)]
-        pub fn new(init_value: bool) -> Self {
-            Self { value: init_value }
+        pub fn new(marketplace: AccountId) -> Self {
+            Self {
+                marketplace: marketplace.into(),
+            }
+        }
+
+        /// Obtiene la dirección del marketplace que consulta este contrato.
+        #[ink(message)]
+        pub fn marketplace(&self) -> AccountId {
+            ink::ToAccountId::to_account_id(&self.marketplace)
         }
 
-        /// Constructor that initializes the `bool` value to `false`.
+        /// Cantidad de órdenes en las que participa una cuenta, ya sea como
+        /// compradora o como vendedora.
         ///
-        /// Constructors can delegate to other constructors.
-        #[ink(constructor)]
-        pub fn default() -> Self {
-            Self::new(Default::default())
+        /// Recorre todas las páginas de los índices de la cuenta en el marketplace.
+        #[ink(message)]
+        pub fn cantidad_ordenes_de_usuario(&self, cuenta: AccountId) -> u32 {
+            contar_ordenes_distintas(&self.ordenes_como_comprador(cuenta), &self.ordenes_como_vendedor(cuenta))
         }
 
-        /// A message that can be called on instantiated contracts.
-        /// This one flips the value of the stored `bool` from `true`
-        /// to `false` and vice versa.
+        /// Órdenes de una cuenta como compradora y como vendedora, por estado, junto
+        /// con el total gastado y cobrado en las órdenes ya liquidadas.
+        ///
+        /// Recorre todas las páginas de los índices de la cuenta en el marketplace.
         #[ink(message)]
-        pub fn flip(&mut self) {
-            self.value = !self.value;
+        pub fn ordenes_por_usuario(&self, cuenta: AccountId) -> ResumenOrdenesUsuario {
+            resumir_ordenes(&self.ordenes_como_comprador(cuenta), &self.ordenes_como_vendedor(cuenta))
         }
 
-        /// Simply returns the current value of our `bool`.
+        /// Ranking de los `n` vendedores con mejor reputación.
+        ///
+        /// Solo participan los usuarios con al menos `MINIMO_CALIFICACIONES_RANKING`
+        /// calificaciones como vendedor. Los empates se ordenan por cuenta.
+        #[ink(message)]
+        pub fn top_vendedores(&self, n: u32) -> Vec<PosicionRanking> {
+            rankear(&self.usuarios(), n, |usuario| &usuario.reputacion_como_vendedor)
+        }
+
+        /// Ranking de los `n` compradores con mejor reputación.
+        ///
+        /// Solo participan los usuarios con al menos `MINIMO_CALIFICACIONES_RANKING`
+        /// calificaciones como comprador. Los empates se ordenan por cuenta.
         #[ink(message)]
-        pub fn get(&self) -> bool {
-            self.value
+        pub fn top_compradores(&self, n: u32) -> Vec<PosicionRanking> {
+            rankear(&self.usuarios(), n, |usuario| &usuario.reputacion_como_comprador)
         }
+
+        /// Los `n` productos con más unidades vendidas en órdenes recibidas.
+        ///
+        /// Los productos sin ventas no aparecen. Los empates se ordenan por id.
+        #[ink(message)]
+        pub fn productos_mas_vendidos(&self, n: u32) -> Vec<Producto> {
+            mas_vendidos(self.productos(), n)
+        }
+
+        /// Estadísticas de ventas de cada categoría con productos publicados,
+        /// ordenadas por nombre de categoría.
+        #[ink(message)]
+        pub fn estadisticas_por_categoria(&self) -> Vec<EstadisticasCategoria> {
+            estadisticas(&self.productos(), &self.ordenes())
+        }
+
+        /// Recorre todas las páginas de órdenes del marketplace.
+        fn ordenes(&self) -> Vec<Orden> {
+            recorrer(|desde, limite| self.marketplace.listar_ordenes(desde, limite))
+        }
+
+        /// Recorre todas las páginas de productos publicados en el marketplace.
+        fn productos(&self) -> Vec<Producto> {
+            recorrer(|desde, limite| self.marketplace.listar_productos(desde, limite))
+        }
+
+        /// Recorre todas las páginas de órdenes de una cuenta como compradora.
+        fn ordenes_como_comprador(&self, cuenta: AccountId) -> Vec<Orden> {
+            recorrer(|desde, limite| self.marketplace.ordenes_como_comprador(cuenta, desde, limite))
+        }
+
+        /// Recorre todas las páginas de órdenes de una cuenta como vendedora.
+        fn ordenes_como_vendedor(&self, cuenta: AccountId) -> Vec<Orden> {
+            recorrer(|desde, limite| self.marketplace.ordenes_como_vendedor(cuenta, desde, limite))
+        }
+
+        /// Recorre todas las páginas de usuarios registrados en el marketplace.
+        fn usuarios(&self) -> Vec<Usuario> {
+            recorrer(|desde, limite| self.marketplace.listar_usuarios(desde, limite))
+        }
+    }
+
+    /// Junta los elementos de todas las páginas de una consulta paginada, pidiendo
+    /// de a `LIMITE_PAGINA` desde el id 0 hasta que no quede página siguiente.
+    fn recorrer<T>(mut pedir: impl FnMut(u32, u32) -> Pagina<T>) -> Vec<T> {
+        let mut elementos = Vec::new();
+        let mut cursor = Some(0);
+        while let Some(desde) = cursor {
+            let pagina = pedir(desde, LIMITE_PAGINA);
+            elementos.extend(pagina.elementos);
+            cursor = pagina.siguiente;
+        }
+        elementos
+    }
+
+    // ────────────────
+    // AGREGACIONES
+    // ────────────────
+
+    /// Ordena a los usuarios por el promedio de la reputación elegida, de mayor a menor,
+    /// descartando los que no llegan al mínimo de calificaciones.
+    fn rankear<F>(usuarios: &[Usuario], n: u32, reputacion: F) -> Vec<PosicionRanking>
+    where
+        F: Fn(&Usuario) -> &Reputacion,
+    {
+        let mut ranking: Vec<PosicionRanking> = usuarios
+            .iter()
+            .filter_map(|usuario| {
+                let reputacion = reputacion(usuario);
+                (reputacion.cantidad_calificaciones >= MINIMO_CALIFICACIONES_RANKING).then(|| PosicionRanking {
+                    cuenta: usuario.direccion,
+                    promedio: reputacion.promedio(),
+                    calificaciones: reputacion.cantidad_calificaciones,
+                })
+            })
+            .collect();
+        ranking.sort_by(|a, b| b.promedio.cmp(&a.promedio).then_with(|| a.cuenta.cmp(&b.cuenta)));
+        ranking.truncate(n as usize);
+        ranking
+    }
+
+    /// Ordena los productos por unidades vendidas, de mayor a menor, descartando
+    /// los que no tienen ventas.
+    fn mas_vendidos(mut productos: Vec<Producto>, n: u32) -> Vec<Producto> {
+        productos.retain(|producto| producto.unidades_vendidas > 0);
+        productos.sort_by(|a, b| b.unidades_vendidas.cmp(&a.unidades_vendidas).then_with(|| a.id.cmp(&b.id)));
+        productos.truncate(n as usize);
+        productos
+    }
+
+    /// Agrupa las órdenes recibidas por la categoría de sus productos.
+    fn estadisticas(productos: &[Producto], ordenes: &[Orden]) -> Vec<EstadisticasCategoria> {
+        let mut por_categoria: BTreeMap<String, EstadisticasCategoria> = BTreeMap::new();
+        let mut categoria_de: BTreeMap<u32, &String> = BTreeMap::new();
+        for producto in productos {
+            categoria_de.insert(producto.id, &producto.categoria);
+            por_categoria
+                .entry(producto.categoria.clone())
+                .or_insert_with(|| EstadisticasCategoria {
+                    categoria: producto.categoria.clone(),
+                    ..Default::default()
+                });
+        }
+
+        // Suma de puntajes al vendedor por categoría, para calcular el promedio al final
+        let mut puntos: BTreeMap<&String, u64> = BTreeMap::new();
+        for orden in ordenes.iter().filter(|orden| orden.estado == EstadoOrden::Recibida) {
+            // Una orden con varias líneas de la misma categoría cuenta una sola vez en ella
+            let mut categorias_de_orden: Vec<&String> = Vec::new();
+            for linea in &orden.lineas {
+                let Some(categoria) = categoria_de.get(&linea.producto_id) else {
+                    continue;
+                };
+                let Some(entrada) = por_categoria.get_mut(*categoria) else {
+                    continue;
+                };
+                entrada.unidades_vendidas = entrada.unidades_vendidas.saturating_add(linea.cantidad);
+                if orden.moneda == Moneda::Nativa {
+                    entrada.volumen = entrada.volumen.saturating_add(linea.monto);
+                }
+                if categorias_de_orden.contains(categoria) {
+                    continue;
+                }
+                categorias_de_orden.push(categoria);
+                entrada.total_ordenes = entrada.total_ordenes.saturating_add(1);
+                if let Some(puntaje) = orden.puntaje_al_vendedor {
+                    entrada.calificaciones = entrada.calificaciones.saturating_add(1);
+                    *puntos.entry(*categoria).or_default() += puntaje as u64;
+                }
+            }
+        }
+
+        por_categoria
+            .into_values()
+            .map(|mut entrada| {
+                if entrada.calificaciones > 0 {
+                    let total = puntos.get(&entrada.categoria).copied().unwrap_or(0);
+                    entrada.promedio_calificacion_vendedor = (total.saturating_mul(ESCALA_PROMEDIO as u64)
+                        / entrada.calificaciones as u64) as u32;
+                }
+                entrada
+            })
+            .collect()
+    }
+
+    /// Monto que el comprador terminó pagando por una orden, incluida la comisión.
+    ///
+    /// Solo las órdenes recibidas liquidan el pago; si la orden se resolvió en una
+    /// disputa dividiendo el pago, se descuenta lo reembolsado al comprador. Los montos
+    /// en tokens PSP22 no se suman al token nativo, así que esas órdenes cuentan 0.
+    fn monto_liquidado(orden: &Orden) -> Balance {
+        if orden.estado != EstadoOrden::Recibida || orden.moneda != Moneda::Nativa {
+            return 0;
+        }
+        match orden.disputa.as_ref().and_then(|disputa| disputa.resolucion.as_ref()) {
+            Some(ResolucionDisputa::Dividir { monto_comprador }) => orden.monto.saturating_sub(*monto_comprador),
+            _ => orden.monto,
+        }
+    }
+
+    /// Monto que el vendedor cobró por una orden: lo liquidado menos la comisión.
+    fn monto_cobrado(orden: &Orden) -> Balance {
+        monto_liquidado(orden).saturating_sub(orden.comision)
+    }
+
+    /// Resume las órdenes de una cuenta en cada rol.
+    fn resumir_ordenes(como_comprador: &[Orden], como_vendedor: &[Orden]) -> ResumenOrdenesUsuario {
+        let mut resumen = ResumenOrdenesUsuario::default();
+        for orden in como_comprador {
+            resumen.como_comprador.contar(&orden.estado);
+            resumen.total_gastado = resumen.total_gastado.saturating_add(monto_liquidado(orden));
+        }
+        for orden in como_vendedor {
+            resumen.como_vendedor.contar(&orden.estado);
+            resumen.total_ganado = resumen.total_ganado.saturating_add(monto_cobrado(orden));
+        }
+        resumen
+    }
+
+    /// Cuenta las órdenes de ambas listas sin repetir las que aparecen en las dos.
+    fn contar_ordenes_distintas(como_comprador: &[Orden], como_vendedor: &[Orden]) -> u32 {
+        let mut ids: Vec<u32> = como_comprador
+            .iter()
+            .chain(como_vendedor)
+            .map(|orden| orden.id)
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.len() as u32
     }
 
-    /// Unit tests in Rust are normally defined within such a `#[cfg(test)]`
-    /// module and test functions are marked with a `#[test]` attribute.
-    /// The below code is technically just normal Rust code.
     #[cfg(test)]
     mod tests {
-        /// Imports all the definitions from the outer scope so we can use them here.
         use super::*;
+        use ink::env::test;
+        use marketplace_principal::{LineaOrden, Permisos, RolUsuario};
+
+        fn orden(id: u32, comprador: AccountId, vendedor: AccountId) -> Orden {
+            Orden::new(id, comprador, vendedor, 0, 1, 100)
+        }
+
+        // Usuario con las calificaciones como vendedor y como comprador indicadas
+        fn usuario(cuenta: AccountId, como_vendedor: &[u8], como_comprador: &[u8]) -> Usuario {
+            let mut usuario = Usuario::new(cuenta, Permisos::de_rol(&RolUsuario::Ambos));
+            como_vendedor.iter().for_each(|p| usuario.reputacion_como_vendedor.registrar(*p));
+            como_comprador.iter().for_each(|p| usuario.reputacion_como_comprador.registrar(*p));
+            usuario
+        }
+
+        fn producto(id: u32, unidades_vendidas: u32) -> Producto {
+            producto_en(id, "Otros", unidades_vendidas)
+        }
+
+        fn producto_en(id: u32, categoria: &str, unidades_vendidas: u32) -> Producto {
+            let mut producto = Producto::new(
+                id,
+                "Producto".into(),
+                "Descripción".into(),
+                100,
+                10,
+                categoria.into(),
+                AccountId::from([0x01; 32]),
+            );
+            producto.unidades_vendidas = unidades_vendidas;
+            producto
+        }
+
+        // Orden recibida del producto indicado, con el puntaje opcional al vendedor
+        fn orden_recibida(id: u32, producto_id: u32, cantidad: u32, puntaje: Option<u8>) -> Orden {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut orden = Orden::new(id, accounts.bob, accounts.alice, producto_id, cantidad, 100 * cantidad as Balance);
+            orden.estado = EstadoOrden::Recibida;
+            orden.puntaje_al_vendedor = puntaje;
+            orden
+        }
 
-        /// We test if the default constructor does its job.
         #[ink::test]
-        fn default_works() {
-            let reportes_view = ReportesView::default();
-            assert_eq!(reportes_view.get(), false);
+        fn recorrer_junta_todas_las_paginas() {
+            let ids: Vec<u32> = (0..LIMITE_PAGINA * 2 + 3).collect();
+            let mut pedidos = Vec::new();
+
+            let todos = recorrer(|desde, limite| {
+                pedidos.push(desde);
+                let hasta = desde.saturating_add(limite).min(ids.len() as u32);
+                Pagina {
+                    elementos: ids[desde as usize..hasta as usize].to_vec(),
+                    siguiente: (hasta < ids.len() as u32).then_some(hasta),
+                }
+            });
+            assert_eq!(todos, ids);
+            assert_eq!(pedidos, vec![0, LIMITE_PAGINA, LIMITE_PAGINA * 2]);
         }
 
-        /// We test a simple use case of our contract.
         #[ink::test]
-        fn it_works() {
-            let mut reportes_view = ReportesView::new(false);
-            assert_eq!(reportes_view.get(), false);
-            reportes_view.flip();
-            assert_eq!(reportes_view.get(), true);
+        fn new_guarda_direccion_del_marketplace() {
+            let marketplace = AccountId::from([0x42; 32]);
+            let reportes = ReportesView::new(marketplace);
+            assert_eq!(reportes.marketplace(), marketplace);
         }
-    }
 
+        #[ink::test]
+        fn contar_ordenes_suma_ambos_roles() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let como_comprador = [orden(0, accounts.alice, accounts.bob), orden(2, accounts.alice, accounts.bob)];
+            let como_vendedor = [orden(1, accounts.charlie, accounts.alice)];
+
+            assert_eq!(contar_ordenes_distintas(&como_comprador, &como_vendedor), 3);
+            assert_eq!(contar_ordenes_distintas(&[], &[]), 0);
+        }
+
+        #[ink::test]
+        fn contar_ordenes_no_repite_ordenes_en_ambos_roles() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            // Dos órdenes, una por rol: Alice compra la 0 a Bob y vende la 1 a Charlie
+            let compra = orden(0, accounts.alice, accounts.bob);
+            let venta = orden(1, accounts.charlie, accounts.alice);
+
+            assert_eq!(contar_ordenes_distintas(&[compra.clone()], &[venta.clone()]), 2);
+            // Un id que llegue en las dos listas se cuenta una sola vez
+            assert_eq!(contar_ordenes_distintas(&[compra.clone()], &[venta, compra]), 2);
+        }
+
+        #[ink::test]
+        fn rankear_ordena_por_promedio_y_desempata_por_cuenta() {
+            let a = AccountId::from([0x0A; 32]);
+            let b = AccountId::from([0x0B; 32]);
+            let c = AccountId::from([0x0C; 32]);
+            let usuarios = [
+                usuario(c, &[5, 5, 4], &[]),
+                usuario(b, &[5, 5, 5], &[]),
+                usuario(a, &[5, 5, 4], &[]),
+            ];
+
+            let ranking = rankear(&usuarios, 5, |u| &u.reputacion_como_vendedor);
+            let cuentas: Vec<AccountId> = ranking.iter().map(|p| p.cuenta).collect();
+            assert_eq!(cuentas, vec![b, a, c]);
+            assert_eq!(ranking[0].promedio, 500);
+            assert_eq!(ranking[1].promedio, 466);
+            assert_eq!(ranking[1].calificaciones, 3);
+        }
+
+        #[ink::test]
+        fn rankear_exige_minimo_de_calificaciones() {
+            let a = AccountId::from([0x0A; 32]);
+            let b = AccountId::from([0x0B; 32]);
+            // `a` tiene buenas notas pero pocas calificaciones como vendedor
+            let usuarios = [usuario(a, &[5, 5], &[3, 3, 3]), usuario(b, &[2, 2, 2], &[])];
+
+            let vendedores = rankear(&usuarios, 5, |u| &u.reputacion_como_vendedor);
+            assert_eq!(vendedores.len(), 1);
+            assert_eq!(vendedores[0].cuenta, b);
+
+            let compradores = rankear(&usuarios, 5, |u| &u.reputacion_como_comprador);
+            assert_eq!(compradores.len(), 1);
+            assert_eq!(compradores[0].cuenta, a);
+        }
 
-    /// This is how you'd write end-to-end (E2E) or integration tests for ink! contracts.
+        #[ink::test]
+        fn rankear_devuelve_como_maximo_n() {
+            let usuarios: Vec<Usuario> = (1..=7u8)
+                .map(|i| usuario(AccountId::from([i; 32]), &[i % 5 + 1; 3], &[]))
+                .collect();
+
+            assert_eq!(rankear(&usuarios, 5, |u| &u.reputacion_como_vendedor).len(), 5);
+            assert!(rankear(&usuarios, 0, |u| &u.reputacion_como_vendedor).is_empty());
+        }
+
+        #[ink::test]
+        fn mas_vendidos_ordena_por_unidades_y_desempata_por_id() {
+            let productos = vec![producto(0, 3), producto(1, 0), producto(2, 7), producto(3, 3)];
+
+            let ranking = mas_vendidos(productos, 10);
+            let ids: Vec<u32> = ranking.iter().map(|p| p.id).collect();
+            // El producto sin ventas queda afuera
+            assert_eq!(ids, vec![2, 0, 3]);
+        }
+
+        #[ink::test]
+        fn mas_vendidos_devuelve_como_maximo_n() {
+            let productos = (0..6).map(|id| producto(id, id + 1)).collect();
+
+            let ranking = mas_vendidos(productos, 2);
+            assert_eq!(ranking.iter().map(|p| p.id).collect::<Vec<_>>(), vec![5, 4]);
+        }
+
+        #[ink::test]
+        fn resumir_ordenes_cuenta_por_estado_y_suma_montos_liquidados() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut enviada = orden(1, accounts.alice, accounts.bob);
+            enviada.estado = EstadoOrden::Enviada;
+            let mut cancelada = orden(2, accounts.alice, accounts.bob);
+            cancelada.estado = EstadoOrden::Cancelada;
+            let como_comprador = [orden(0, accounts.alice, accounts.bob), enviada, cancelada];
+            // `orden_recibida` usa a Alice como vendedora y cobra 100 por unidad
+            let como_vendedor = [orden_recibida(3, 0, 2, None), orden_recibida(4, 0, 1, Some(5))];
+
+            let resumen = resumir_ordenes(&como_comprador, &como_vendedor);
+            assert_eq!(
+                resumen.como_comprador,
+                ConteoPorEstado { pendientes: 1, enviadas: 1, canceladas: 1, ..Default::default() }
+            );
+            assert_eq!(resumen.como_vendedor.recibidas, 2);
+            // Lo pendiente, enviado o cancelado no cuenta como gastado
+            assert_eq!(resumen.total_gastado, 0);
+            assert_eq!(resumen.total_ganado, 300);
+        }
+
+        #[ink::test]
+        fn monto_liquidado_descuenta_reembolso_de_disputa_dividida() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut dividida = orden_recibida(0, 0, 10, None);
+            dividida.disputa = Some(marketplace_principal::Disputa {
+                iniciador: accounts.bob,
+                motivo_hash: Hash::from([0x07; 32]),
+                resolucion: Some(ResolucionDisputa::Dividir { monto_comprador: 400 }),
+            });
+
+            assert_eq!(monto_liquidado(&dividida), 600);
+            let mut en_disputa = orden_recibida(1, 0, 10, None);
+            en_disputa.estado = EstadoOrden::EnDisputa;
+            assert_eq!(monto_liquidado(&en_disputa), 0);
+        }
+
+        #[ink::test]
+        fn total_ganado_descuenta_la_comision_cobrada() {
+            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+            // 5% de comisión sobre una venta de 200
+            let mut con_comision = orden_recibida(0, 0, 2, None);
+            con_comision.comision = 10;
+
+            let resumen = resumir_ordenes(&[], &[con_comision.clone(), orden_recibida(1, 0, 1, None)]);
+            assert_eq!(resumen.total_ganado, 290);
+            // El comprador pagó el monto completo
+            let resumen = resumir_ordenes(&[con_comision], &[]);
+            assert_eq!(resumen.total_gastado, 200);
+            assert_eq!(resumir_ordenes(&[], &[orden(2, accounts.bob, accounts.alice)]).total_ganado, 0);
+        }
+
+        #[ink::test]
+        fn estadisticas_reparten_ordenes_de_varios_productos() {
+            let productos = [producto_en(0, "Tecnología", 0), producto_en(1, "Tecnología", 0), producto_en(2, "Hogar", 0)];
+            let mut orden = orden_recibida(0, 0, 1, Some(4));
+            orden.lineas = vec![
+                LineaOrden { producto_id: 0, cantidad: 1, monto: 100 },
+                LineaOrden { producto_id: 1, cantidad: 2, monto: 200 },
+                LineaOrden { producto_id: 2, cantidad: 3, monto: 300 },
+            ];
+
+            let estadisticas = estadisticas(&productos, &[orden]);
+            let hogar = &estadisticas[0];
+            assert_eq!((hogar.total_ordenes, hogar.unidades_vendidas, hogar.volumen), (1, 3, 300));
+            // Las dos líneas de tecnología son una sola orden y una sola calificación
+            let tecnologia = &estadisticas[1];
+            assert_eq!((tecnologia.total_ordenes, tecnologia.unidades_vendidas, tecnologia.volumen), (1, 3, 300));
+            assert_eq!(tecnologia.calificaciones, 1);
+            assert_eq!(tecnologia.promedio_calificacion_vendedor, 400);
+        }
+
+        #[ink::test]
+        fn ordenes_en_token_no_suman_montos_nativos() {
+            let mut en_token = orden_recibida(0, 0, 2, Some(5));
+            en_token.moneda = Moneda::Psp22(AccountId::from([0x70; 32]));
+
+            assert_eq!(monto_liquidado(&en_token), 0);
+            let estadisticas = estadisticas(&[producto_en(0, "Tecnología", 0)], &[en_token]);
+            assert_eq!(estadisticas[0].total_ordenes, 1);
+            assert_eq!(estadisticas[0].unidades_vendidas, 2);
+            assert_eq!(estadisticas[0].volumen, 0);
+        }
+
+        #[ink::test]
+        fn estadisticas_agrupan_ordenes_recibidas_por_categoria() {
+            let productos = [
+                producto_en(0, "Tecnología", 0),
+                producto_en(1, "Hogar", 0),
+                producto_en(2, "Tecnología", 0),
+                producto_en(3, "Libros", 0),
+            ];
+            let mut cancelada = orden_recibida(3, 1, 9, Some(1));
+            cancelada.estado = EstadoOrden::Cancelada;
+            let ordenes = [
+                orden_recibida(0, 0, 2, Some(5)),
+                orden_recibida(1, 2, 1, Some(4)),
+                orden_recibida(2, 2, 3, None),
+                orden_recibida(4, 1, 1, Some(3)),
+                cancelada,
+            ];
+
+            let estadisticas = estadisticas(&productos, &ordenes);
+            let categorias: Vec<&str> = estadisticas.iter().map(|e| e.categoria.as_str()).collect();
+            assert_eq!(categorias, vec!["Hogar", "Libros", "Tecnología"]);
+
+            let hogar = &estadisticas[0];
+            assert_eq!(hogar.total_ordenes, 1);
+            assert_eq!(hogar.unidades_vendidas, 1);
+            assert_eq!(hogar.promedio_calificacion_vendedor, 300);
+
+            // Categoría sin ventas
+            assert_eq!(estadisticas[1], EstadisticasCategoria { categoria: "Libros".into(), ..Default::default() });
+
+            let tecnologia = &estadisticas[2];
+            assert_eq!(tecnologia.total_ordenes, 3);
+            assert_eq!(tecnologia.unidades_vendidas, 6);
+            assert_eq!(tecnologia.volumen, 600);
+            assert_eq!(tecnologia.calificaciones, 2);
+            assert_eq!(tecnologia.promedio_calificacion_vendedor, 450);
+        }
+    }
+
+    /// Tests de integración contra un nodo con `pallet-contracts`.
     ///
-    /// When running these you need to make sure that you:
-    /// - Compile the tests with the `e2e-tests` feature flag enabled (`--features e2e-tests`)
-    /// - Are running a Substrate node which contains `pallet-contracts` in the background
+    /// Para correrlos:
+    /// - Compilar con la feature `e2e-tests` (`--features e2e-tests`).
+    /// - Tener un nodo de Substrate con `pallet-contracts` corriendo en segundo plano.
     #[cfg(all(test, feature = "e2e-tests"))]
     mod e2e_tests {
-        /// Imports all the definitions from the outer scope so we can use them here.
         use super::*;
-
-        /// A helper function used for calling contract messages.
         use ink_e2e::ContractsBackend;
+        use marketplace_principal::{MarketplacePrincipal, MarketplacePrincipalRef, RolUsuario};
 
-        /// The End-to-End test `Result` type.
         type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-        /// We test that we can upload and instantiate the contract using its default constructor.
+        /// Despliega el marketplace y un `ReportesView` que apunta a él.
         #[ink_e2e::test]
-        async fn default_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+        async fn reporta_ordenes_del_marketplace(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
             // Given
-            let mut constructor = ReportesViewRef::default();
+            let mut constructor = MarketplacePrincipalRef::new();
+            let marketplace = client
+                .instantiate("marketplace_principal", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("instantiate marketplace failed");
+            let mut marketplace_call = marketplace.call_builder::<MarketplacePrincipal>();
 
-            // When
-            let contract = client
+            let mut constructor = ReportesViewRef::new(marketplace.account_id);
+            let reportes = client
                 .instantiate("reportes_view", &ink_e2e::alice(), &mut constructor)
                 .submit()
                 .await
-                .expect("instantiate failed");
-            let call_builder = contract.call_builder::<ReportesView>();
+                .expect("instantiate reportes_view failed");
+            let reportes_call = reportes.call_builder::<ReportesView>();
+
+            // When
+            let registrar = marketplace_call.registrar_usuario(RolUsuario::Vendedor);
+            client.call(&ink_e2e::alice(), &registrar).submit().await.expect("registrar vendedor failed");
+            let publicar = marketplace_call.publicar_producto(
+                "Celular".into(),
+                "Un buen celular".into(),
+                1000,
+                5,
+                "Tecnología".into(),
+            );
+            client.call(&ink_e2e::alice(), &publicar).submit().await.expect("publicar failed");
+
+            let registrar = marketplace_call.registrar_usuario(RolUsuario::Comprador);
+            client.call(&ink_e2e::bob(), &registrar).submit().await.expect("registrar comprador failed");
+            let comprar = marketplace_call.crear_orden(0, 2);
+            client
+                .call(&ink_e2e::bob(), &comprar)
+                .value(2000)
+                .submit()
+                .await
+                .expect("crear_orden failed");
 
             // Then
-            let get = call_builder.get();
-            let get_result = client.call(&ink_e2e::alice(), &get).dry_run().await?;
-            assert!(matches!(get_result.return_value(), false));
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let consulta = reportes_call.cantidad_ordenes_de_usuario(bob);
+            let resultado = client.call(&ink_e2e::bob(), &consulta).dry_run().await?;
+            assert_eq!(resultado.return_value(), 1);
+
+            let consulta = reportes_call.marketplace();
+            let resultado = client.call(&ink_e2e::bob(), &consulta).dry_run().await?;
+            assert_eq!(resultado.return_value(), marketplace.account_id);
 
             Ok(())
         }
 
-        /// We test that we can read and write a value from the on-chain contract.
+        /// Las órdenes de una cuenta se cuentan aunque ocupen más de una página.
         #[ink_e2e::test]
-        async fn it_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+        async fn reporta_ordenes_de_usuario_en_varias_paginas(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
             // Given
-            let mut constructor = ReportesViewRef::new(false);
-            let contract = client
-                .instantiate("reportes_view", &ink_e2e::bob(), &mut constructor)
+            let mut constructor = MarketplacePrincipalRef::new();
+            let marketplace = client
+                .instantiate("marketplace_principal", &ink_e2e::alice(), &mut constructor)
                 .submit()
                 .await
-                .expect("instantiate failed");
-            let mut call_builder = contract.call_builder::<ReportesView>();
-
-            let get = call_builder.get();
-            let get_result = client.call(&ink_e2e::bob(), &get).dry_run().await?;
-            assert!(matches!(get_result.return_value(), false));
+                .expect("instantiate marketplace failed");
+            let mut marketplace_call = marketplace.call_builder::<MarketplacePrincipal>();
 
-            // When
-            let flip = call_builder.flip();
-            let _flip_result = client
-                .call(&ink_e2e::bob(), &flip)
+            let mut constructor = ReportesViewRef::new(marketplace.account_id);
+            let reportes = client
+                .instantiate("reportes_view", &ink_e2e::alice(), &mut constructor)
                 .submit()
                 .await
-                .expect("flip failed");
+                .expect("instantiate reportes_view failed");
+            let reportes_call = reportes.call_builder::<ReportesView>();
+
+            let registrar = marketplace_call.registrar_usuario(RolUsuario::Vendedor);
+            client.call(&ink_e2e::alice(), &registrar).submit().await.expect("registrar vendedor failed");
+            let cantidad = LIMITE_PAGINA + 1;
+            let publicar = marketplace_call.publicar_producto(
+                "Lapicera".into(),
+                "Azul".into(),
+                10,
+                cantidad,
+                "Librería".into(),
+            );
+            client.call(&ink_e2e::alice(), &publicar).submit().await.expect("publicar failed");
+            let registrar = marketplace_call.registrar_usuario(RolUsuario::Comprador);
+            client.call(&ink_e2e::bob(), &registrar).submit().await.expect("registrar comprador failed");
+
+            // When
+            for _ in 0..cantidad {
+                let comprar = marketplace_call.crear_orden(0, 1);
+                client
+                    .call(&ink_e2e::bob(), &comprar)
+                    .value(10)
+                    .submit()
+                    .await
+                    .expect("crear_orden failed");
+            }
 
             // Then
-            let get = call_builder.get();
-            let get_result = client.call(&ink_e2e::bob(), &get).dry_run().await?;
-            assert!(matches!(get_result.return_value(), true));
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let consulta = reportes_call.cantidad_ordenes_de_usuario(bob);
+            let resultado = client.call(&ink_e2e::bob(), &consulta).dry_run().await?;
+            assert_eq!(resultado.return_value(), cantidad);
+
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let consulta = reportes_call.ordenes_por_usuario(alice);
+            let resultado = client.call(&ink_e2e::alice(), &consulta).dry_run().await?;
+            assert_eq!(resultado.return_value().como_vendedor.pendientes, cantidad);
 
             Ok(())
         }